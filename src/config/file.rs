@@ -0,0 +1,428 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+use super::{
+    validate_account_role, validate_dir_path, validate_duration, validate_endpoint,
+    validate_external_id, validate_file_path, validate_ip, validate_mfa_code, validate_mfa_serial,
+    validate_possible_value, validate_region, validate_role_arn, validate_role_duration,
+    validate_session_name, AGGREGATE_FIELDS, EVENT_STATUS_CODES, EVENT_TYPE_CATEGORIES, PARTITIONS,
+};
+
+/// Contents of the configuration file.
+///
+/// Keys are the long names of the command line arguments. Arguments which can be repeated
+/// take a list, with a plural name.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(super) struct FileConfig {
+    listen: Option<String>,
+    debug: Option<u64>,
+    quiet: Option<bool>,
+    regions: Option<Vec<String>>,
+    services: Option<Vec<String>>,
+    categories: Option<Vec<String>>,
+    statuses: Option<Vec<String>>,
+    max_start_age: Option<u64>,
+    max_end_age: Option<u64>,
+    max_last_updated_age: Option<u64>,
+    partition: Option<String>,
+    role: Option<String>,
+    role_region: Option<String>,
+    health_endpoint: Option<String>,
+    sts_endpoint: Option<String>,
+    role_external_id: Option<String>,
+    role_session_name: Option<String>,
+    role_duration: Option<u64>,
+    role_policy: Option<String>,
+    role_mfa_serial: Option<String>,
+    role_mfa_code: Option<String>,
+    profile: Option<String>,
+    credentials_file: Option<String>,
+    web_identity: Option<bool>,
+    web_identity_token_file: Option<String>,
+    web_identity_role: Option<String>,
+    account_roles: Option<Vec<String>>,
+    probe_roles: Option<Vec<String>>,
+    organization: Option<bool>,
+    affected_entities: Option<bool>,
+    affected_accounts: Option<bool>,
+    aggregates_by: Option<Vec<String>>,
+    no_events: Option<bool>,
+    event_details: Option<bool>,
+    event_types: Option<bool>,
+    tls_key: Option<String>,
+    tls_cert: Option<String>,
+    record_dir: Option<String>,
+    replay_dir: Option<String>,
+    refresh_interval: Option<u64>,
+    refresh_timeout: Option<u64>,
+    endpoint_timeout: Option<u64>,
+}
+
+impl FileConfig {
+    /// Read a YAML file if it has a `.yaml` or `.yml` extension, TOML otherwise.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let result = match Path::new(path).extension().and_then(OsStr::to_str) {
+            Some("yaml") | Some("yml") => {
+                serde_yaml::from_str(&content).map_err(|err| err.to_string())
+            }
+            _ => toml::from_str(&content).map_err(|err| err.to_string()),
+        };
+        result.map_err(|err| format!("{}: {}", path, err))
+    }
+
+    /// Convert the values to their command line form, checking them with the same validators.
+    pub fn into_args(self) -> Result<FileArgs, String> {
+        let mut args = FileArgs::default();
+        args.single("listen_host", "listen", self.listen, validate_ip)?;
+        // Same as giving `--debug` this many times
+        let debug = self.debug.filter(|occurrences| *occurrences > 0);
+        args.single("debug", "debug", debug, validate_debug_occurrences)?;
+        args.flag("quiet", self.quiet);
+        args.multiple("region", "regions", self.regions, validate_region)?;
+        args.multiple("service", "services", self.services, |_| Ok(()))?;
+        args.multiple("category", "categories", self.categories, |value| {
+            validate_possible_value(&EVENT_TYPE_CATEGORIES, value)
+        })?;
+        args.multiple("status", "statuses", self.statuses, |value| {
+            validate_possible_value(&EVENT_STATUS_CODES, value)
+        })?;
+        args.single(
+            "max_start_age",
+            "max-start-age",
+            self.max_start_age,
+            validate_duration,
+        )?;
+        args.single(
+            "max_end_age",
+            "max-end-age",
+            self.max_end_age,
+            validate_duration,
+        )?;
+        args.single(
+            "max_last_updated_age",
+            "max-last-updated-age",
+            self.max_last_updated_age,
+            validate_duration,
+        )?;
+        args.single("partition", "partition", self.partition, |value| {
+            validate_possible_value(&PARTITIONS, value)
+        })?;
+        args.single("role", "role", self.role, validate_role_arn)?;
+        args.single(
+            "role_region",
+            "role-region",
+            self.role_region,
+            validate_region,
+        )?;
+        args.single(
+            "health_endpoint",
+            "health-endpoint",
+            self.health_endpoint,
+            validate_endpoint,
+        )?;
+        args.single(
+            "sts_endpoint",
+            "sts-endpoint",
+            self.sts_endpoint,
+            validate_endpoint,
+        )?;
+        args.single(
+            "role_external_id",
+            "role-external-id",
+            self.role_external_id,
+            validate_external_id,
+        )?;
+        args.single(
+            "role_session_name",
+            "role-session-name",
+            self.role_session_name,
+            validate_session_name,
+        )?;
+        args.single(
+            "role_duration",
+            "role-duration",
+            self.role_duration,
+            validate_role_duration,
+        )?;
+        args.single(
+            "role_policy",
+            "role-policy",
+            self.role_policy,
+            validate_file_path,
+        )?;
+        args.single(
+            "role_mfa_serial",
+            "role-mfa-serial",
+            self.role_mfa_serial,
+            validate_mfa_serial,
+        )?;
+        args.single(
+            "role_mfa_code",
+            "role-mfa-code",
+            self.role_mfa_code,
+            validate_mfa_code,
+        )?;
+        args.single("profile", "profile", self.profile, |_| Ok(()))?;
+        args.single(
+            "credentials_file",
+            "credentials-file",
+            self.credentials_file,
+            validate_file_path,
+        )?;
+        args.flag("web_identity", self.web_identity);
+        args.single(
+            "web_identity_token_file",
+            "web-identity-token-file",
+            self.web_identity_token_file,
+            validate_file_path,
+        )?;
+        args.single(
+            "web_identity_role",
+            "web-identity-role",
+            self.web_identity_role,
+            validate_role_arn,
+        )?;
+        args.multiple(
+            "account_role",
+            "account-roles",
+            self.account_roles,
+            validate_account_role,
+        )?;
+        args.multiple(
+            "probe_role",
+            "probe-roles",
+            self.probe_roles,
+            validate_role_arn,
+        )?;
+        args.flag("organization", self.organization);
+        args.flag("affected_entities", self.affected_entities);
+        args.flag("affected_accounts", self.affected_accounts);
+        args.multiple(
+            "aggregate_by",
+            "aggregates-by",
+            self.aggregates_by,
+            |value| validate_possible_value(&AGGREGATE_FIELDS, value),
+        )?;
+        args.flag("no_events", self.no_events);
+        args.flag("event_details", self.event_details);
+        args.flag("event_types", self.event_types);
+        args.single("tls_key", "tls-key", self.tls_key, validate_file_path)?;
+        args.single("tls_cert", "tls-cert", self.tls_cert, validate_file_path)?;
+        args.single("record_dir", "record-dir", self.record_dir, |_| Ok(()))?;
+        args.single(
+            "replay_dir",
+            "replay-dir",
+            self.replay_dir,
+            validate_dir_path,
+        )?;
+        args.single(
+            "refresh_interval",
+            "refresh-interval",
+            self.refresh_interval,
+            validate_duration,
+        )?;
+        args.single(
+            "refresh_timeout",
+            "refresh-timeout",
+            self.refresh_timeout,
+            validate_duration,
+        )?;
+        args.single(
+            "endpoint_timeout",
+            "endpoint-timeout",
+            self.endpoint_timeout,
+            validate_duration,
+        )?;
+        Ok(args)
+    }
+}
+
+/// Values from the configuration file, indexed by argument name.
+///
+/// Flags which are set have an empty list of values.
+#[derive(Debug, Default)]
+pub(super) struct FileArgs {
+    values: HashMap<&'static str, Vec<String>>,
+}
+
+impl FileArgs {
+    pub fn values_of(&self, name: &str) -> Option<&Vec<String>> {
+        self.values.get(name)
+    }
+
+    fn single<T, V>(
+        &mut self,
+        name: &'static str,
+        key: &str,
+        value: Option<T>,
+        validator: V,
+    ) -> Result<(), String>
+    where
+        T: ToString,
+        V: Fn(String) -> Result<(), String>,
+    {
+        if let Some(value) = value {
+            let value = value.to_string();
+            validator(value.clone())
+                .map_err(|err| format!("Invalid value for `{}`: {}", key, err))?;
+            self.values.insert(name, vec![value]);
+        }
+        Ok(())
+    }
+
+    fn multiple<V>(
+        &mut self,
+        name: &'static str,
+        key: &str,
+        values: Option<Vec<String>>,
+        validator: V,
+    ) -> Result<(), String>
+    where
+        V: Fn(String) -> Result<(), String>,
+    {
+        if let Some(values) = values {
+            for (index, value) in values.iter().enumerate() {
+                validator(value.clone())
+                    .map_err(|err| format!("Invalid value for `{}[{}]`: {}", key, index, err))?;
+            }
+            self.values.insert(name, values);
+        }
+        Ok(())
+    }
+
+    fn flag(&mut self, name: &'static str, value: Option<bool>) {
+        if value == Some(true) {
+            self.values.insert(name, vec![]);
+        }
+    }
+}
+
+fn validate_debug_occurrences(occurrences: String) -> Result<(), String> {
+    match occurrences.as_str() {
+        "1" | "2" => Ok(()),
+        _ => Err("must be 1 for debug logging, or 2 for trace logging".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Config;
+    use super::*;
+    use std::time::Duration;
+
+    static TOML_CONFIG: &str = "tests/fixtures/config/exporter.toml";
+    static YAML_CONFIG: &str = "tests/fixtures/config/exporter.yaml";
+    static TRACE_CONFIG: &str = "tests/fixtures/config/trace.toml";
+
+    fn parse(args: &[&str]) -> Result<Config, clap::Error> {
+        Config::parse(["aws_health_exporter"].iter().chain(args))
+    }
+
+    fn file_error(content: &str) -> String {
+        toml::from_str::<FileConfig>(content)
+            .map_err(|err| err.to_string())
+            .and_then(FileConfig::into_args)
+            .unwrap_err()
+    }
+
+    #[test]
+    fn toml_and_yaml_files_are_read() {
+        for path in [TOML_CONFIG, YAML_CONFIG] {
+            let config = parse(&["--config", path]).unwrap();
+            assert_eq!(
+                config.role.as_deref(),
+                Some("arn:aws:iam::123456789012:role/aws-health-exporter")
+            );
+            assert!(config.use_organization);
+            // Global events are always included
+            let regions = ["eu-central-1", "eu-west-1", "global"];
+            assert_eq!(config.regions, Some(regions.map(str::to_string).to_vec()));
+            assert_eq!(config.refresh_interval, Duration::from_secs(300));
+        }
+    }
+
+    #[test]
+    fn command_line_takes_precedence() {
+        let config = parse(&[
+            "--config",
+            TOML_CONFIG,
+            "--region",
+            "us-east-1",
+            "--refresh-interval",
+            "60",
+        ])
+        .unwrap();
+        let regions = ["global", "us-east-1"];
+        assert_eq!(config.regions, Some(regions.map(str::to_string).to_vec()));
+        assert_eq!(config.refresh_interval, Duration::from_secs(60));
+        // Values which aren't given on the command line still come from the file
+        assert_eq!(
+            config.services,
+            Some(vec!["EC2".to_string(), "RDS".to_string()])
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(file_error("organisation = true").contains("unknown field `organisation`"));
+    }
+
+    #[test]
+    fn errors_point_at_the_key() {
+        assert_eq!(
+            file_error("refresh-interval = 0"),
+            "Invalid value for `refresh-interval`: must be greater than 0"
+        );
+        assert!(file_error("regions = [\"eu-west-1\", \"mars-1\"]")
+            .starts_with("Invalid value for `regions[1]`"));
+        assert!(file_error("aggregates-by = [\"service\"]")
+            .starts_with("Invalid value for `aggregates-by[0]`"));
+        assert_eq!(
+            file_error("debug = 3"),
+            "Invalid value for `debug`: must be 1 for debug logging, or 2 for trace logging"
+        );
+    }
+
+    #[test]
+    fn repeated_arguments_have_plural_keys() {
+        let file_config: FileConfig =
+            toml::from_str("aggregates-by = [\"eventTypeCategory\"]").unwrap();
+        let args = file_config.into_args().unwrap();
+        assert_eq!(
+            args.values_of("aggregate_by"),
+            Some(&vec!["eventTypeCategory".to_string()])
+        );
+        assert!(file_error("aggregate-by = [\"eventTypeCategory\"]")
+            .contains("unknown field `aggregate-by`"));
+    }
+
+    #[test]
+    fn log_level_is_read_from_the_file() {
+        let config = parse(&["--config", TRACE_CONFIG]).unwrap();
+        assert_eq!(config.log_level, log::LevelFilter::Trace);
+        // A single `--debug` on the command line takes precedence
+        let config = parse(&["--config", TRACE_CONFIG, "--debug"]).unwrap();
+        assert_eq!(config.log_level, log::LevelFilter::Debug);
+        let err = parse(&["--config", TRACE_CONFIG, "--quiet"]).unwrap_err();
+        assert!(err.message.contains("`quiet` cannot be used with `debug`"));
+    }
+
+    #[test]
+    fn constraints_apply_across_file_and_command_line() {
+        let err = parse(&[
+            "--config",
+            TOML_CONFIG,
+            "--aggregate-by",
+            "eventTypeCategory",
+        ])
+        .unwrap_err();
+        assert!(err
+            .message
+            .contains("`aggregate-by` cannot be used with `organization`"));
+    }
+}
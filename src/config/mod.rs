@@ -1,39 +1,238 @@
-use clap::{crate_authors, crate_description, crate_name, crate_version, App, AppSettings, Arg};
+use clap::{
+    crate_authors, crate_description, crate_version, App, AppSettings, Arg, ArgMatches, ErrorKind,
+};
+use file::{FileArgs, FileConfig};
 use regex::Regex;
 use rusoto_core::Region;
+use std::ffi::OsString;
 use std::net::SocketAddr;
 use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
+use warp::http::Uri;
+
+mod file;
+mod partition;
+
+pub use partition::Partition;
+use partition::PARTITIONS;
 
 static DEFAULT_IP: &str = "[::]:9679";
+static DEFAULT_PARTITION: &str = "aws";
+static DEFAULT_REFRESH_INTERVAL: &str = "60";
+static DEFAULT_REFRESH_TIMEOUT: &str = "300";
+static DEFAULT_ENDPOINT_TIMEOUT: &str = "30";
+static DEFAULT_ROLE_SESSION_NAME: &str = "aws-health-exporter";
+// Bounds of the duration of role sessions accepted by STS
+static MIN_ROLE_DURATION: u64 = 900;
+static MAX_ROLE_DURATION: u64 = 43200;
+static DEFAULT_EVENT_TYPE_CATEGORIES: [&str; 2] = ["issue", "scheduledChange"];
+static EVENT_TYPE_CATEGORIES: [&str; 4] = [
+    "accountNotification",
+    "investigation",
+    "issue",
+    "scheduledChange",
+];
+static EVENT_STATUS_CODES: [&str; 3] = ["closed", "open", "upcoming"];
+// Fields supported by DescribeEventAggregates
+static AGGREGATE_FIELDS: [&str; 1] = ["eventTypeCategory"];
+// Constraints between arguments, checked once the command line and configuration file are merged
+// An argument requires at least one of the others
+static REQUIRES: [(&str, &[&str]); 14] = [
+    (
+        "role_region",
+        &["role", "account_role", "probe_role", "web_identity"],
+    ),
+    (
+        "sts_endpoint",
+        &["role", "account_role", "probe_role", "web_identity"],
+    ),
+    ("role_external_id", &["role", "account_role", "probe_role"]),
+    (
+        "role_duration",
+        &["role", "account_role", "probe_role", "web_identity"],
+    ),
+    (
+        "role_policy",
+        &["role", "account_role", "probe_role", "web_identity"],
+    ),
+    ("role_mfa_serial", &["role", "account_role", "probe_role"]),
+    ("role_mfa_serial", &["role_mfa_code"]),
+    ("role_mfa_code", &["role_mfa_serial"]),
+    ("web_identity_token_file", &["web_identity"]),
+    ("web_identity_role", &["web_identity"]),
+    ("affected_accounts", &["organization"]),
+    ("no_events", &["aggregate_by"]),
+    ("tls_key", &["tls_cert"]),
+    ("tls_cert", &["tls_key"]),
+];
+// Same as REQUIRES, only when the second argument is present
+static REQUIRES_WITH: [(&str, &str, &[&str]); 1] = [
+    // Details of account-specific organization events are queried for each affected account
+    ("event_details", "organization", &["affected_accounts"]),
+];
+static CONFLICTS: [(&str, &str); 17] = [
+    ("quiet", "debug"),
+    ("profile", "web_identity"),
+    ("credentials_file", "web_identity"),
+    ("account_role", "role"),
+    ("account_role", "affected_accounts"),
+    // An MFA code can only be used once, so it can't be shared by the sessions of several roles
+    ("account_role", "role_mfa_code"),
+    ("probe_role", "role_mfa_code"),
+    ("aggregate_by", "organization"),
+    ("no_events", "affected_entities"),
+    ("no_events", "affected_accounts"),
+    ("no_events", "event_details"),
+    ("record_dir", "replay_dir"),
+    // Only the events are recorded
+    ("replay_dir", "affected_entities"),
+    ("replay_dir", "affected_accounts"),
+    ("replay_dir", "aggregate_by"),
+    ("replay_dir", "event_details"),
+    ("replay_dir", "event_types"),
+];
 
-#[derive(Debug)]
-pub struct TLS {
+#[derive(Clone, Debug)]
+pub struct Tls {
     pub key: String,
     pub cert: String,
 }
 
-#[derive(Debug)]
+/// Role to assume in one of the accounts to query
+#[derive(Clone, Debug)]
+pub struct AccountRole {
+    pub role: String,
+    pub account_id: String,
+    pub name: Option<String>,
+}
+
+impl FromStr for AccountRole {
+    type Err = String;
+
+    /// Parse `[NAME=]ROLE_ARN`. Role names may contain `=`, so the name is only split off
+    /// when the value doesn't start with an ARN.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, role) = match value.split_once('=') {
+            Some((name, role)) if !value.starts_with("arn:") => (Some(name), role),
+            _ => (None, value),
+        };
+        if name == Some("") {
+            return Err("account name must not be empty".to_string());
+        }
+        validate_role_arn(role.to_string())?;
+        Ok(Self {
+            role: role.to_string(),
+            // Works because the ARN is validated
+            account_id: role_account_id(role).unwrap().to_string(),
+            name: name.map(str::to_string),
+        })
+    }
+}
+
+/// Options of the sessions of assumed roles
+#[derive(Clone, Debug)]
+pub struct AssumeRoleOptions {
+    pub session_name: String,
+    pub external_id: Option<String>,
+    pub duration: Option<Duration>,
+    /// Inline session policy, as JSON
+    pub policy: Option<String>,
+    pub mfa_serial: Option<String>,
+    pub mfa_code: Option<String>,
+}
+
+/// Identity obtained from an OIDC token, such as the token of a Kubernetes service account
+#[derive(Clone, Debug)]
+pub struct WebIdentity {
+    pub token_file: String,
+    pub role: String,
+}
+
+#[derive(Clone, Debug)]
 pub struct Config {
     pub socket_addr: SocketAddr,
     pub log_level: log::LevelFilter,
+    pub partition: Partition,
     pub role: Option<String>,
     pub role_region: Option<String>,
+    pub health_endpoint: Option<String>,
+    pub sts_endpoint: Option<String>,
+    pub assume_role_options: AssumeRoleOptions,
+    pub web_identity: Option<WebIdentity>,
+    pub profile: Option<String>,
+    pub credentials_file: Option<String>,
+    pub account_roles: Option<Vec<AccountRole>>,
+    /// Roles which can be given to `/probe`, besides those of `account_roles`
+    pub probe_roles: Vec<String>,
     pub use_organization: bool,
+    pub affected_entities: bool,
+    pub affected_accounts: bool,
+    pub aggregate_fields: Option<Vec<String>>,
+    pub export_events: bool,
+    pub event_details: bool,
+    pub event_types: bool,
     pub regions: Option<Vec<String>>,
     pub services: Option<Vec<String>>,
-    pub tls_config: Option<TLS>,
+    pub event_type_categories: Vec<String>,
+    pub event_status_codes: Option<Vec<String>>,
+    pub max_start_age: Option<Duration>,
+    pub max_end_age: Option<Duration>,
+    pub max_last_updated_age: Option<Duration>,
+    pub refresh_interval: Duration,
+    pub refresh_timeout: Duration,
+    pub endpoint_timeout: Duration,
+    pub tls_config: Option<Tls>,
+    pub record_dir: Option<String>,
+    pub replay_dir: Option<String>,
     pub version: String,
-    name: String,
 }
 
 impl Config {
     pub fn from_args() -> Self {
+        Self::parse(std::env::args_os()).unwrap_or_else(|err| err.exit())
+    }
+
+    /// Build the configuration from the given arguments and the configuration file they point to.
+    ///
+    /// Arguments given on the command line take precedence over the configuration file.
+    pub fn parse<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Self::parse_with_color(args, AppSettings::ColorAuto)
+    }
+
+    /// Same as `parse`, with errors meant to be returned rather than printed to a terminal
+    pub fn parse_without_color<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        Self::parse_with_color(args, AppSettings::ColorNever)
+    }
+
+    fn parse_with_color<I, T>(args: I, color: AppSettings) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
         let matches = App::new("AWS Health Exporter")
             .author(crate_authors!())
             .version(crate_version!())
             .about(crate_description!())
-            .settings(&[AppSettings::ColoredHelp, AppSettings::ColorAuto])
+            .settings(&[AppSettings::ColoredHelp, color])
+            .arg(
+                Arg::with_name("config")
+                    .short("c")
+                    .long("config")
+                    .value_name("FILE")
+                    .help("Path to a TOML or YAML configuration file")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_file_path),
+            )
             .arg(
                 Arg::with_name("listen_host")
                     .short("l")
@@ -82,6 +281,60 @@ impl Config {
                     .help("Service for which to retrieve events")
                     .multiple(true),
             )
+            .arg(
+                Arg::with_name("category")
+                    .long("category")
+                    .takes_value(true)
+                    .required(false)
+                    .help("Event category to retrieve [default: issue, scheduledChange]")
+                    .multiple(true)
+                    .possible_values(&EVENT_TYPE_CATEGORIES),
+            )
+            .arg(
+                Arg::with_name("status")
+                    .long("status")
+                    .takes_value(true)
+                    .required(false)
+                    .help("Status for which to retrieve events [default: all]")
+                    .multiple(true)
+                    .possible_values(&EVENT_STATUS_CODES),
+            )
+            .arg(
+                Arg::with_name("max_start_age")
+                    .long("max-start-age")
+                    .value_name("SECONDS")
+                    .help("Only retrieve events which started less than this long ago")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_duration),
+            )
+            .arg(
+                Arg::with_name("max_end_age")
+                    .long("max-end-age")
+                    .value_name("SECONDS")
+                    .help("Only retrieve events which ended less than this long ago")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_duration),
+            )
+            .arg(
+                Arg::with_name("max_last_updated_age")
+                    .long("max-last-updated-age")
+                    .value_name("SECONDS")
+                    .help("Only retrieve events which were updated less than this long ago")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_duration),
+            )
+            .arg(
+                Arg::with_name("partition")
+                    .long("partition")
+                    .help("AWS partition of the accounts to query")
+                    .takes_value(true)
+                    .required(false)
+                    .default_value(DEFAULT_PARTITION)
+                    .possible_values(&PARTITIONS),
+            )
             .arg(
                 Arg::with_name("role")
                     .long("role")
@@ -90,15 +343,151 @@ impl Config {
                     .required(false)
                     .validator(validate_role_arn),
             )
+            .arg(
+                Arg::with_name("account_role")
+                    .long("account-role")
+                    .value_name("[NAME=]ROLE_ARN")
+                    .help("Assume IAM Role in one of several accounts to query, with an optional name")
+                    .takes_value(true)
+                    .required(false)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .validator(validate_account_role),
+            )
+            .arg(
+                Arg::with_name("probe_role")
+                    .long("probe-role")
+                    .value_name("ROLE_ARN")
+                    .help("Allow /probe to assume this IAM Role, besides those of --account-role")
+                    .takes_value(true)
+                    .required(false)
+                    .multiple(true)
+                    .number_of_values(1)
+                    .validator(validate_role_arn),
+            )
             .arg(
                 Arg::with_name("role_region")
                     .long("role-region")
                     .help("Endpoint to use for calls to STS")
                     .takes_value(true)
                     .required(false)
-                    .requires("role")
                     .validator(validate_region),
             )
+            .arg(
+                Arg::with_name("health_endpoint")
+                    .long("health-endpoint")
+                    .value_name("URL")
+                    .help("Endpoint to use for calls to AWS Health, instead of the public one")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_endpoint),
+            )
+            .arg(
+                Arg::with_name("sts_endpoint")
+                    .long("sts-endpoint")
+                    .value_name("URL")
+                    .help("Endpoint to use for calls to STS when assuming roles")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_endpoint),
+            )
+            .arg(
+                Arg::with_name("role_external_id")
+                    .long("role-external-id")
+                    .value_name("ID")
+                    .help("External ID to pass when assuming roles")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_external_id),
+            )
+            .arg(
+                Arg::with_name("role_session_name")
+                    .long("role-session-name")
+                    .value_name("NAME")
+                    .help("Name of the sessions of assumed roles")
+                    .takes_value(true)
+                    .required(false)
+                    .default_value(DEFAULT_ROLE_SESSION_NAME)
+                    .validator(validate_session_name),
+            )
+            .arg(
+                Arg::with_name("role_duration")
+                    .long("role-duration")
+                    .value_name("SECONDS")
+                    .help("Duration of the sessions of assumed roles [default: 3600]")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_role_duration),
+            )
+            .arg(
+                Arg::with_name("role_policy")
+                    .long("role-policy")
+                    .value_name("FILE")
+                    .help("Path to a JSON policy further restricting the sessions of assumed roles")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_file_path),
+            )
+            .arg(
+                Arg::with_name("role_mfa_serial")
+                    .long("role-mfa-serial")
+                    .value_name("SERIAL")
+                    .help("Serial number or ARN of the MFA device to use when assuming roles")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_mfa_serial),
+            )
+            .arg(
+                Arg::with_name("role_mfa_code")
+                    .long("role-mfa-code")
+                    .value_name("CODE")
+                    .help("Code from the MFA device")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_mfa_code),
+            )
+            .arg(
+                Arg::with_name("profile")
+                    .long("profile")
+                    .value_name("NAME")
+                    .help("Profile from the credentials file [default: $AWS_PROFILE or default]")
+                    .takes_value(true)
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("credentials_file")
+                    .long("credentials-file")
+                    .value_name("FILE")
+                    .help("Path to the credentials file [default: ~/.aws/credentials]")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_file_path),
+            )
+            .arg(
+                Arg::with_name("web_identity")
+                    .long("web-identity")
+                    .help("Get credentials with AssumeRoleWithWebIdentity, before assuming any other role")
+                    .takes_value(false)
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("web_identity_token_file")
+                    .long("web-identity-token-file")
+                    .value_name("FILE")
+                    .help("Path to the web identity token [default: $AWS_WEB_IDENTITY_TOKEN_FILE]")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_file_path),
+            )
+            .arg(
+                Arg::with_name("web_identity_role")
+                    .long("web-identity-role")
+                    .value_name("ROLE_ARN")
+                    .help("Role to assume with the web identity token [default: $AWS_ROLE_ARN]")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_role_arn),
+            )
             .arg(
                 Arg::with_name("organization")
                     .long("organization")
@@ -106,13 +495,57 @@ impl Config {
                     .takes_value(false)
                     .required(false),
             )
+            .arg(
+                Arg::with_name("affected_entities")
+                    .long("affected-entities")
+                    .help("Query the entities affected by open events")
+                    .takes_value(false)
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("affected_accounts")
+                    .long("affected-accounts")
+                    .help("Export one series per account affected by organization events")
+                    .takes_value(false)
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("aggregate_by")
+                    .long("aggregate-by")
+                    .value_name("FIELD")
+                    .help("Export event counts aggregated by this field")
+                    .takes_value(true)
+                    .required(false)
+                    .multiple(true)
+                    .possible_values(&AGGREGATE_FIELDS),
+            )
+            .arg(
+                Arg::with_name("no_events")
+                    .long("no-events")
+                    .help("Only export event counts, not the individual events")
+                    .takes_value(false)
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("event_details")
+                    .long("event-details")
+                    .help("Export the latest description of open events")
+                    .takes_value(false)
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("event_types")
+                    .long("event-types")
+                    .help("Export the event types and check the services to filter on at startup")
+                    .takes_value(false)
+                    .required(false),
+            )
             .arg(
                 Arg::with_name("tls_key")
                     .long("tls-key")
                     .help("Path to TLS certificate key")
                     .takes_value(true)
                     .required(false)
-                    .requires("tls_cert")
                     .validator(validate_file_path),
             )
             .arg(
@@ -121,10 +554,65 @@ impl Config {
                     .help("Path to TLS certificate")
                     .takes_value(true)
                     .required(false)
-                    .requires("tls_key")
                     .validator(validate_file_path),
             )
-            .get_matches();
+            .arg(
+                Arg::with_name("record_dir")
+                    .long("record-dir")
+                    .value_name("DIR")
+                    .help("Write the pages of events returned by the AWS API to this directory")
+                    .takes_value(true)
+                    .required(false),
+            )
+            .arg(
+                Arg::with_name("replay_dir")
+                    .long("replay-dir")
+                    .value_name("DIR")
+                    .help("Serve the pages of events recorded in this directory instead of calling the AWS API")
+                    .takes_value(true)
+                    .required(false)
+                    .validator(validate_dir_path),
+            )
+            .arg(
+                Arg::with_name("refresh_interval")
+                    .long("refresh-interval")
+                    .value_name("SECONDS")
+                    .help("Interval between two refreshes of the events from the AWS API")
+                    .takes_value(true)
+                    .required(false)
+                    .default_value(DEFAULT_REFRESH_INTERVAL)
+                    .validator(validate_duration),
+            )
+            .arg(
+                Arg::with_name("refresh_timeout")
+                    .long("refresh-timeout")
+                    .value_name("SECONDS")
+                    .help("Time after which a refresh of the events which hasn't completed is deemed failed")
+                    .takes_value(true)
+                    .required(false)
+                    .default_value(DEFAULT_REFRESH_TIMEOUT)
+                    .validator(validate_duration),
+            )
+            .arg(
+                Arg::with_name("endpoint_timeout")
+                    .long("endpoint-timeout")
+                    .value_name("SECONDS")
+                    .help("Time to wait for each response of the AWS Health API before deeming the endpoint failed")
+                    .takes_value(true)
+                    .required(false)
+                    .default_value(DEFAULT_ENDPOINT_TIMEOUT)
+                    .validator(validate_duration),
+            )
+            .get_matches_from_safe(args)?;
+
+        let file_args = match matches.value_of("config") {
+            Some(path) => FileConfig::load(path)
+                .and_then(FileConfig::into_args)
+                .map_err(|err| clap::Error::with_description(&err, ErrorKind::ValueValidation))?,
+            None => FileArgs::default(),
+        };
+        let matches = MergedArgs { matches, file_args };
+        matches.check_constraints()?;
 
         let log_level = if matches.occurrences_of("debug") >= 2 {
             log::LevelFilter::Trace
@@ -149,32 +637,333 @@ impl Config {
             services
         });
 
+        let event_type_categories = matches.values_of_lossy("category").map_or_else(
+            || {
+                DEFAULT_EVENT_TYPE_CATEGORIES
+                    .iter()
+                    .map(|category| category.to_string())
+                    .collect()
+            },
+            |mut categories| {
+                categories.sort_unstable();
+                categories.dedup();
+                categories
+            },
+        );
+
+        let event_status_codes = matches.values_of_lossy("status").map(|mut status_codes| {
+            status_codes.sort_unstable();
+            status_codes.dedup();
+            status_codes
+        });
+
+        // Works because the arguments are validated
+        let duration_of = |name| {
+            matches
+                .value_of(name)
+                .map(|seconds| Duration::from_secs(seconds.parse().unwrap()))
+        };
+        let max_start_age = duration_of("max_start_age");
+        let max_end_age = duration_of("max_end_age");
+        let max_last_updated_age = duration_of("max_last_updated_age");
+
+        let aggregate_fields = matches.values_of_lossy("aggregate_by").map(|mut fields| {
+            fields.sort_unstable();
+            fields.dedup();
+            fields
+        });
+
         let tls_config = match (matches.value_of("tls_key"), matches.value_of("tls_cert")) {
-            (Some(key), Some(cert)) => Some(TLS {
+            (Some(key), Some(cert)) => Some(Tls {
                 key: key.to_string(),
                 cert: cert.to_string(),
             }),
             _ => None,
         };
 
+        let policy = matches
+            .value_of("role_policy")
+            .map(read_role_policy)
+            .transpose()
+            .map_err(|err| clap::Error::with_description(&err, ErrorKind::ValueValidation))?;
+        let assume_role_options = AssumeRoleOptions {
+            // Works because the argument has a default value
+            session_name: matches.value_of("role_session_name").unwrap().to_string(),
+            external_id: matches.value_of("role_external_id").map(str::to_string),
+            duration: duration_of("role_duration"),
+            policy,
+            mfa_serial: matches.value_of("role_mfa_serial").map(str::to_string),
+            mfa_code: matches.value_of("role_mfa_code").map(str::to_string),
+        };
+
+        let web_identity = if matches.is_present("web_identity") {
+            let token_file = env_value_of(
+                &matches,
+                "web_identity_token_file",
+                "AWS_WEB_IDENTITY_TOKEN_FILE",
+                validate_file_path,
+            )?;
+            let role = env_value_of(
+                &matches,
+                "web_identity_role",
+                "AWS_ROLE_ARN",
+                validate_role_arn,
+            )?;
+            Some(WebIdentity { token_file, role })
+        } else {
+            None
+        };
+
+        // Works because the arguments are validated
+        let account_roles = matches
+            .values_of_lossy("account_role")
+            .map(|values| {
+                let mut account_roles: Vec<AccountRole> = vec![];
+                for value in values {
+                    let account_role: AccountRole = value.parse().unwrap();
+                    if account_roles
+                        .iter()
+                        .any(|known| known.account_id == account_role.account_id)
+                    {
+                        return Err(clap::Error::with_description(
+                            &format!(
+                                "Account {} is given more than once in `account-role`",
+                                account_role.account_id
+                            ),
+                            ErrorKind::ValueValidation,
+                        ));
+                    }
+                    account_roles.push(account_role);
+                }
+                Ok(account_roles)
+            })
+            .transpose()?;
+
         let use_organization = matches.is_present("organization");
+        let affected_entities = matches.is_present("affected_entities");
+        let affected_accounts = matches.is_present("affected_accounts");
+
+        // Works because the arguments are validated
+        let refresh_interval = Duration::from_secs(
+            matches
+                .value_of("refresh_interval")
+                .unwrap()
+                .parse()
+                .unwrap(),
+        );
+        let refresh_timeout = Duration::from_secs(
+            matches
+                .value_of("refresh_timeout")
+                .unwrap()
+                .parse()
+                .unwrap(),
+        );
+        let endpoint_timeout = Duration::from_secs(
+            matches
+                .value_of("endpoint_timeout")
+                .unwrap()
+                .parse()
+                .unwrap(),
+        );
 
-        Self {
-            /// Works because the argument is validated
+        let config = Self {
+            // Works because the argument is validated
             socket_addr: matches.value_of("listen_host").unwrap().parse().unwrap(),
             log_level,
             version: crate_version!().to_string(),
-            name: crate_name!().to_string(),
             regions,
             services,
+            event_type_categories,
+            event_status_codes,
+            max_start_age,
+            max_end_age,
+            max_last_updated_age,
+            refresh_interval,
+            refresh_timeout,
+            endpoint_timeout,
             role: matches.value_of("role").map(|s| s.to_string()),
             role_region: matches.value_of("role_region").map(|s| s.to_string()),
+            health_endpoint: matches.value_of("health_endpoint").map(str::to_string),
+            sts_endpoint: matches.value_of("sts_endpoint").map(str::to_string),
+            assume_role_options,
+            web_identity,
+            profile: matches.value_of("profile").map(str::to_string),
+            credentials_file: matches.value_of("credentials_file").map(str::to_string),
+            account_roles,
+            probe_roles: matches.values_of_lossy("probe_role").unwrap_or_default(),
             tls_config,
+            record_dir: matches.value_of("record_dir").map(str::to_string),
+            replay_dir: matches.value_of("replay_dir").map(str::to_string),
             use_organization,
+            affected_entities,
+            affected_accounts,
+            aggregate_fields,
+            export_events: !matches.is_present("no_events"),
+            event_details: matches.is_present("event_details"),
+            event_types: matches.is_present("event_types"),
+            // Works because the argument has a default value and is validated
+            partition: matches.value_of("partition").unwrap().parse().unwrap(),
+        };
+        config
+            .check_partition()
+            .map_err(|err| clap::Error::with_description(&err, ErrorKind::ValueValidation))?;
+        Ok(config)
+    }
+
+    /// Check that the roles and regions are all in the configured partition
+    fn check_partition(&self) -> Result<(), String> {
+        let roles = self
+            .role
+            .iter()
+            .chain(
+                self.account_roles
+                    .iter()
+                    .flatten()
+                    .map(|account| &account.role),
+            )
+            .chain(self.probe_roles.iter())
+            .chain(
+                self.web_identity
+                    .iter()
+                    .map(|web_identity| &web_identity.role),
+            );
+        for role in roles {
+            self.partition.check_role(role)?;
+        }
+        let regions = self
+            .role_region
+            .iter()
+            .chain(self.regions.iter().flatten())
+            .filter(|region| !["all", "global"].contains(&region.as_str()));
+        for region in regions {
+            self.partition.check_region(region)?;
+        }
+        Ok(())
+    }
+}
+
+/// Arguments from the command line, completed by those from the configuration file
+struct MergedArgs<'a> {
+    matches: ArgMatches<'a>,
+    file_args: FileArgs,
+}
+
+impl<'a> MergedArgs<'a> {
+    fn given_on_command_line(&self, name: &str) -> bool {
+        self.matches.occurrences_of(name) > 0
+    }
+
+    fn value_of(&self, name: &str) -> Option<&str> {
+        match self.file_args.values_of(name) {
+            Some(values) if !self.given_on_command_line(name) => values.first().map(String::as_str),
+            // Falls back to the default value, if any
+            _ => self.matches.value_of(name),
+        }
+    }
+
+    fn values_of_lossy(&self, name: &str) -> Option<Vec<String>> {
+        match self.file_args.values_of(name) {
+            Some(values) if !self.given_on_command_line(name) => Some(values.to_vec()),
+            _ => self.matches.values_of_lossy(name),
+        }
+    }
+
+    fn is_present(&self, name: &str) -> bool {
+        self.matches.is_present(name) || self.file_args.values_of(name).is_some()
+    }
+
+    /// Number of times a flag was given, which the file holds as a number for counted flags
+    fn occurrences_of(&self, name: &str) -> u64 {
+        match self.file_args.values_of(name) {
+            Some(values) if !self.given_on_command_line(name) => values
+                .first()
+                .map_or(1, |occurrences| occurrences.parse().unwrap()),
+            _ => self.matches.occurrences_of(name),
+        }
+    }
+
+    fn check_constraints(&self) -> Result<(), clap::Error> {
+        for (name, required) in REQUIRES.iter() {
+            if self.is_present(name) && !required.iter().any(|required| self.is_present(required)) {
+                let required: Vec<String> = required
+                    .iter()
+                    .map(|required| format!("`{}`", arg_key(required)))
+                    .collect();
+                return Err(clap::Error::with_description(
+                    &format!("`{}` requires {}", arg_key(name), required.join(" or ")),
+                    ErrorKind::MissingRequiredArgument,
+                ));
+            }
         }
+        for (name, condition, required) in REQUIRES_WITH.iter() {
+            if self.is_present(name)
+                && self.is_present(condition)
+                && !required.iter().any(|required| self.is_present(required))
+            {
+                let required: Vec<String> = required
+                    .iter()
+                    .map(|required| format!("`{}`", arg_key(required)))
+                    .collect();
+                return Err(clap::Error::with_description(
+                    &format!(
+                        "`{}` requires {} when used with `{}`",
+                        arg_key(name),
+                        required.join(" or "),
+                        arg_key(condition)
+                    ),
+                    ErrorKind::MissingRequiredArgument,
+                ));
+            }
+        }
+        for (name, conflicting) in CONFLICTS.iter() {
+            if self.is_present(name) && self.is_present(conflicting) {
+                return Err(clap::Error::with_description(
+                    &format!(
+                        "`{}` cannot be used with `{}`",
+                        arg_key(name),
+                        arg_key(conflicting)
+                    ),
+                    ErrorKind::ArgumentConflict,
+                ));
+            }
+        }
+        Ok(())
     }
 }
 
+/// Value of an argument, falling back to an environment variable checked with the same validator
+fn env_value_of<V>(
+    matches: &MergedArgs,
+    name: &str,
+    env_var: &str,
+    validator: V,
+) -> Result<String, clap::Error>
+where
+    V: Fn(String) -> Result<(), String>,
+{
+    let value = match matches.value_of(name) {
+        Some(value) => value.to_string(),
+        None => std::env::var(env_var).map_err(|_| {
+            clap::Error::with_description(
+                &format!("`{}` or {} must be set", arg_key(name), env_var),
+                ErrorKind::MissingRequiredArgument,
+            )
+        })?,
+    };
+    validator(value.clone()).map_err(|err| {
+        clap::Error::with_description(
+            &format!("Invalid value for {}: {}", env_var, err),
+            ErrorKind::ValueValidation,
+        )
+    })?;
+    Ok(value)
+}
+
+/// Name of an argument as written on the command line and in the configuration file
+fn arg_key(name: &str) -> String {
+    name.replace('_', "-")
+}
+
 fn validate_ip(ip: String) -> Result<(), String> {
     // The map at the end is required because Clap expects validators to return Result<(), String>.
     // https://docs.rs/clap/2.33.1/clap/struct.Arg.html#method.validator
@@ -197,15 +986,121 @@ fn validate_region(region: String) -> Result<(), String> {
 /// The 20 character limit comes from an error raised when a short string is given.
 /// The regular expression comes from the official docs:
 /// https://docs.aws.amazon.com/IAM/latest/UserGuide/list_identityandaccessmanagement.html#identityandaccessmanagement-resources-for-iam-policies
-fn validate_role_arn(role_arn: String) -> Result<(), String> {
+pub(crate) fn validate_role_arn(role_arn: String) -> Result<(), String> {
     if role_arn.len() < 20 {
         return Err("Must have length greater than or equal to 20".to_string());
     }
-    let arn_regex = Regex::new(r"(?i:arn:aws:iam::\d{12}:role/.*)").unwrap();
-    if arn_regex.is_match(&role_arn) {
+    if role_account_id(&role_arn).is_some() {
+        Ok(())
+    } else {
+        Err("must be of the form `arn:PARTITION:iam::123456789012:role/something`".to_string())
+    }
+}
+
+/// Account of a role, if the ARN is well-formed
+fn role_account_id(role_arn: &str) -> Option<&str> {
+    let arn_regex = Regex::new(r"^(?i:arn:aws(?:-cn|-us-gov)?:iam::(\d{12}):role/.+)$").unwrap();
+    arn_regex
+        .captures(role_arn)
+        .and_then(|captures| captures.get(1))
+        .map(|account_id| account_id.as_str())
+}
+
+fn validate_account_role(account_role: String) -> Result<(), String> {
+    account_role.parse::<AccountRole>().map(|_| ())
+}
+
+/// Validates that an endpoint is an absolute HTTP(S) URL
+fn validate_endpoint(endpoint: String) -> Result<(), String> {
+    let uri = endpoint.parse::<Uri>().map_err(|err| format!("{}", err))?;
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http"), Some(_)) | (Some("https"), Some(_)) => Ok(()),
+        _ => Err("must be of the form `https://host[:port]`".to_string()),
+    }
+}
+
+/// Validates an external ID against the constraints of AssumeRole
+fn validate_external_id(external_id: String) -> Result<(), String> {
+    let external_id_regex = Regex::new(r"^[\w+=,.@:/-]*$").unwrap();
+    if (2..=1224).contains(&external_id.len()) && external_id_regex.is_match(&external_id) {
+        Ok(())
+    } else {
+        Err("must be 2 to 1224 letters, digits or any of `+=,.@:/-`".to_string())
+    }
+}
+
+/// Validates a session name against the constraints of AssumeRole
+fn validate_session_name(session_name: String) -> Result<(), String> {
+    let session_name_regex = Regex::new(r"^[\w+=,.@-]*$").unwrap();
+    if (2..=64).contains(&session_name.len()) && session_name_regex.is_match(&session_name) {
+        Ok(())
+    } else {
+        Err("must be 2 to 64 letters, digits or any of `+=,.@-`".to_string())
+    }
+}
+
+fn validate_role_duration(seconds: String) -> Result<(), String> {
+    match seconds.parse::<u64>() {
+        Ok(seconds) if (MIN_ROLE_DURATION..=MAX_ROLE_DURATION).contains(&seconds) => Ok(()),
+        Ok(_) => Err(format!(
+            "must be between {} and {}",
+            MIN_ROLE_DURATION, MAX_ROLE_DURATION
+        )),
+        Err(err) => Err(format!("{}", err)),
+    }
+}
+
+/// Validates either the serial number of a hardware device or the ARN of a virtual device
+fn validate_mfa_serial(serial: String) -> Result<(), String> {
+    let serial_regex = Regex::new(r"^[\w+=/:,.@-]*$").unwrap();
+    if (9..=256).contains(&serial.len()) && serial_regex.is_match(&serial) {
+        Ok(())
+    } else {
+        Err("must be 9 to 256 letters, digits or any of `+=/:,.@-`".to_string())
+    }
+}
+
+fn validate_mfa_code(code: String) -> Result<(), String> {
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err("must be 6 digits".to_string())
+    }
+}
+
+/// Read a session policy, checking that it's valid JSON
+fn read_role_policy(path: &str) -> Result<String, String> {
+    let policy = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    serde_json::from_str::<serde_json::Value>(&policy)
+        .map_err(|err| format!("Invalid policy in {}: {}", path, err))?;
+    Ok(policy)
+}
+
+fn validate_duration(seconds: String) -> Result<(), String> {
+    match seconds.parse::<u64>() {
+        Ok(0) => Err("must be greater than 0".to_string()),
+        Ok(_) => Ok(()),
+        Err(err) => Err(format!("{}", err)),
+    }
+}
+
+fn validate_possible_value(possible_values: &[&str], value: String) -> Result<(), String> {
+    if possible_values.contains(&value.as_str()) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} isn't one of {}",
+            value,
+            possible_values.join(", ")
+        ))
+    }
+}
+
+fn validate_dir_path(dir_path: String) -> Result<(), String> {
+    if Path::new(&dir_path).is_dir() {
         Ok(())
     } else {
-        Err("must be of the form `arn:aws:iam::123456789012:role/something`".to_string())
+        Err(format!("{} is not a directory", dir_path))
     }
 }
 
@@ -216,3 +1111,70 @@ fn validate_file_path(file_path: String) -> Result<(), String> {
         Err(format!("{} is not a file", file_path))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, clap::Error> {
+        Config::parse(["aws_health_exporter"].iter().chain(args))
+    }
+
+    #[test]
+    fn role_arns_are_anchored() {
+        let role = "arn:aws:iam::123456789012:role/aws-health-exporter";
+        assert!(validate_role_arn(role.to_string()).is_ok());
+        assert!(validate_role_arn(format!("foo:{}", role)).is_err());
+        assert!(validate_role_arn("arn:aws:iam::123456789012:role/".to_string()).is_err());
+
+        let account_role: AccountRole = format!("production={}", role).parse().unwrap();
+        assert_eq!(account_role.account_id, "123456789012");
+        assert_eq!(account_role.name.as_deref(), Some("production"));
+        assert!(format!("production=foo:{}", role)
+            .parse::<AccountRole>()
+            .is_err());
+    }
+
+    #[test]
+    fn mfa_code_is_only_used_with_a_single_role() {
+        let mfa_args = [
+            "--role-mfa-serial",
+            "arn:aws:iam::123456789012:mfa/user",
+            "--role-mfa-code",
+            "123456",
+        ];
+        let role = "arn:aws:iam::123456789012:role/aws-health-exporter";
+
+        assert!(parse(&[&["--role", role][..], &mfa_args].concat()).is_ok());
+        let err = parse(&[&["--account-role", role][..], &mfa_args].concat()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ArgumentConflict);
+        assert!(err
+            .message
+            .contains("`account-role` cannot be used with `role-mfa-code`"));
+        let err = parse(&[&["--probe-role", role][..], &mfa_args].concat()).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn organization_event_details_require_affected_accounts() {
+        assert!(parse(&["--event-details"]).is_ok());
+        assert!(parse(&["--organization", "--affected-accounts", "--event-details"]).is_ok());
+        let err = parse(&["--organization", "--event-details"]).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MissingRequiredArgument);
+        assert!(err.message.contains(
+            "`event-details` requires `affected-accounts` when used with `organization`"
+        ));
+    }
+
+    #[test]
+    fn probe_roles_are_in_the_partition() {
+        let role = "arn:aws-cn:iam::123456789012:role/aws-health-exporter";
+
+        assert!(parse(&["--partition", "aws-cn", "--probe-role", role]).is_ok());
+        let err = parse(&["--probe-role", role]).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::ValueValidation);
+        assert!(err
+            .message
+            .contains(&format!("Role {} is in partition aws-cn, not aws", role)));
+    }
+}
@@ -0,0 +1,172 @@
+use rusoto_core::Region;
+use std::fmt;
+use std::str::FromStr;
+
+pub(super) static PARTITIONS: [&str; 3] = ["aws", "aws-cn", "aws-us-gov"];
+
+/// Group of AWS regions, each having its own global AWS Health endpoint
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Partition {
+    #[default]
+    Aws,
+    AwsCn,
+    AwsUsGov,
+}
+
+impl Partition {
+    /// Region of the global AWS Health endpoint of the partition
+    pub fn health_region(self) -> Region {
+        match self {
+            Self::Aws => Region::UsEast1,
+            Self::AwsCn => Region::CnNorthwest1,
+            Self::AwsUsGov => Region::UsGovWest1,
+        }
+    }
+
+    /// Region of the passive AWS Health endpoint, for partitions which have one
+    pub fn health_failover_region(self) -> Option<Region> {
+        match self {
+            Self::Aws => Some(Region::UsEast2),
+            Self::AwsCn | Self::AwsUsGov => None,
+        }
+    }
+
+    pub fn of_region(region: &str) -> Self {
+        if region.starts_with("cn-") {
+            Self::AwsCn
+        } else if region.starts_with("us-gov-") {
+            Self::AwsUsGov
+        } else {
+            Self::Aws
+        }
+    }
+
+    /// Partition of an ARN which is known to be valid
+    pub fn of_arn(arn: &str) -> Self {
+        arn.split(':')
+            .nth(1)
+            .and_then(|partition| partition.parse().ok())
+            .unwrap_or(Self::Aws)
+    }
+
+    /// Check that the role can be assumed from this partition
+    pub fn check_role(self, role: &str) -> Result<(), String> {
+        match Self::of_arn(role) {
+            partition if partition == self => Ok(()),
+            partition => Err(format!(
+                "Role {} is in partition {}, not {}",
+                role, partition, self
+            )),
+        }
+    }
+
+    pub fn check_region(self, region: &str) -> Result<(), String> {
+        match Self::of_region(region) {
+            partition if partition == self => Ok(()),
+            partition => Err(format!(
+                "Region {} is in partition {}, not {}",
+                region, partition, self
+            )),
+        }
+    }
+}
+
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(partition: &str) -> Result<Self, Self::Err> {
+        match partition {
+            "aws" => Ok(Self::Aws),
+            "aws-cn" => Ok(Self::AwsCn),
+            "aws-us-gov" => Ok(Self::AwsUsGov),
+            _ => Err(format!("Unknown partition {}", partition)),
+        }
+    }
+}
+
+impl fmt::Display for Partition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Aws => write!(f, "aws"),
+            Self::AwsCn => write!(f, "aws-cn"),
+            Self::AwsUsGov => write!(f, "aws-us-gov"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_checked_against_the_partition() {
+        let roles = [
+            ("arn:aws:iam::123456789012:role/health", Partition::Aws),
+            ("arn:aws-cn:iam::123456789012:role/health", Partition::AwsCn),
+            (
+                "arn:aws-us-gov:iam::123456789012:role/health",
+                Partition::AwsUsGov,
+            ),
+        ];
+        for (role, role_partition) in roles {
+            for partition in [Partition::Aws, Partition::AwsCn, Partition::AwsUsGov] {
+                let result = partition.check_role(role);
+                if partition == role_partition {
+                    assert_eq!(result, Ok(()));
+                } else {
+                    let message = format!(
+                        "Role {} is in partition {}, not {}",
+                        role, role_partition, partition
+                    );
+                    assert_eq!(result, Err(message));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn regions_are_checked_against_the_partition() {
+        let regions = [
+            ("us-east-1", Partition::Aws),
+            ("eu-west-3", Partition::Aws),
+            ("global", Partition::Aws),
+            ("cn-north-1", Partition::AwsCn),
+            ("cn-northwest-1", Partition::AwsCn),
+            ("us-gov-west-1", Partition::AwsUsGov),
+            ("us-gov-east-1", Partition::AwsUsGov),
+        ];
+        for (region, region_partition) in regions {
+            assert_eq!(region_partition.check_region(region), Ok(()));
+        }
+        assert_eq!(
+            Partition::AwsCn.check_region("us-east-1"),
+            Err("Region us-east-1 is in partition aws, not aws-cn".to_string())
+        );
+        assert_eq!(
+            Partition::Aws.check_region("us-gov-west-1"),
+            Err("Region us-gov-west-1 is in partition aws-us-gov, not aws".to_string())
+        );
+    }
+
+    #[test]
+    fn health_endpoints_are_in_their_partition() {
+        for partition in [Partition::Aws, Partition::AwsCn, Partition::AwsUsGov] {
+            let region = partition.health_region();
+            assert_eq!(partition.check_region(region.name()), Ok(()));
+            if let Some(failover_region) = partition.health_failover_region() {
+                assert_eq!(partition.check_region(failover_region.name()), Ok(()));
+            }
+        }
+        assert_eq!(Partition::AwsCn.health_region(), Region::CnNorthwest1);
+        assert_eq!(Partition::AwsUsGov.health_region(), Region::UsGovWest1);
+        assert_eq!(Partition::AwsCn.health_failover_region(), None);
+    }
+
+    #[test]
+    fn unknown_partitions_are_rejected() {
+        assert_eq!(
+            "aws-iso".parse::<Partition>(),
+            Err("Unknown partition aws-iso".to_string())
+        );
+    }
+}
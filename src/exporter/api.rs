@@ -0,0 +1,116 @@
+use crate::exporter::cache::Cache;
+use crate::scraper::EventSummary;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+use warp::http::StatusCode;
+
+// URL parameters filtering the events, each taking a comma-separated list of values
+static FILTERS: [&str; 4] = ["region", "service", "category", "status"];
+
+#[derive(Serialize)]
+struct EventsResponse<'a> {
+    /// Whether the last refresh was successful, the events being those of the last successful one
+    success: bool,
+    last_refresh: Option<DateTime<Utc>>,
+    events: Vec<&'a EventSummary>,
+}
+
+/// Handler for the events endpoint, serving the events of the last refresh.
+pub async fn handle_events(
+    cache: Arc<Cache>,
+    params: HashMap<String, String>,
+) -> StdResult<impl warp::Reply, Infallible> {
+    if let Some(name) = params.keys().find(|name| !FILTERS.contains(&name.as_str())) {
+        let message = format!(
+            "Unknown parameter `{}`, expected one of {}",
+            name,
+            FILTERS.join(", ")
+        );
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&json!({ "error": message })),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let snapshot = cache.get();
+    let events = snapshot
+        .event_metrics
+        .iter()
+        .flat_map(|event_metrics| &event_metrics.event_summaries)
+        .filter(|event| matches(event, &params))
+        .collect();
+    let response = EventsResponse {
+        success: snapshot.success,
+        last_refresh: snapshot.last_refresh,
+        events,
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        StatusCode::OK,
+    ))
+}
+
+fn matches(event: &EventSummary, params: &HashMap<String, String>) -> bool {
+    params.iter().all(|(name, values)| {
+        let value = match name.as_str() {
+            "region" => &event.region,
+            "service" => &event.service,
+            "category" => &event.event_type_category,
+            "status" => &event.status,
+            _ => return true,
+        };
+        values.split(',').any(|expected| expected == value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(region: &str, status: &str) -> EventSummary {
+        EventSummary {
+            arn: None,
+            account_id: None,
+            account_name: None,
+            region: region.to_string(),
+            availability_zone: None,
+            service: "EC2".to_string(),
+            event_type_category: "issue".to_string(),
+            event_type_code: "AWS_EC2_OPERATIONAL_ISSUE".to_string(),
+            status: status.to_string(),
+            scope: None,
+            start_time: None,
+            end_time: None,
+            last_updated_time: None,
+            affected_accounts: vec![],
+            description: None,
+        }
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn filters_take_any_of_their_values() {
+        let params = params(&[("region", "eu-west-1,us-east-1")]);
+        assert!(matches(&event("us-east-1", "open"), &params));
+        assert!(!matches(&event("eu-central-1", "open"), &params));
+    }
+
+    #[test]
+    fn events_match_all_filters() {
+        let params = params(&[("region", "us-east-1"), ("status", "open,upcoming")]);
+        assert!(matches(&event("us-east-1", "upcoming"), &params));
+        assert!(!matches(&event("us-east-1", "closed"), &params));
+        assert!(matches(&event("eu-west-1", "closed"), &HashMap::new()));
+    }
+}
@@ -0,0 +1,84 @@
+use crate::scraper::EventMetrics;
+use chrono::{DateTime, Utc};
+use prometheus::IntGauge;
+use std::sync::RwLock;
+
+/// State of the events as of the last refresh from the AWS API
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    /// Events from the last successful refresh, one set per account
+    pub event_metrics: Vec<EventMetrics>,
+    /// Whether the last refresh attempt was successful
+    pub success: bool,
+    /// Time of the last successful refresh
+    pub last_refresh: Option<DateTime<Utc>>,
+    /// Whether the organizational view of each account is enabled, when querying organization events
+    pub organization_views: Vec<IntGauge>,
+}
+
+/// Holds the last snapshot so that HTTP requests don't have to call the AWS API
+#[derive(Default)]
+pub struct Cache {
+    snapshot: RwLock<Snapshot>,
+}
+
+impl Cache {
+    pub fn get(&self) -> Snapshot {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    pub fn set_events(&self, event_metrics: Vec<EventMetrics>) {
+        let mut snapshot = self.snapshot.write().unwrap();
+        snapshot.event_metrics = event_metrics;
+        snapshot.success = true;
+        snapshot.last_refresh = Some(Utc::now());
+    }
+
+    pub fn set_organization_views(&self, organization_views: Vec<IntGauge>) {
+        self.snapshot.write().unwrap().organization_views = organization_views;
+    }
+
+    /// Keeps the previous events around, their age is exposed by the last refresh timestamp
+    pub fn set_error(&self) {
+        self.snapshot.write().unwrap().success = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::scraper::fake_backend::FakeBackend;
+    use crate::scraper::Scraper;
+    use std::sync::Arc;
+
+    #[test]
+    fn successful_refreshes_are_timestamped() {
+        let cache = Cache::default();
+        assert!(!cache.get().success);
+        assert_eq!(cache.get().last_refresh, None);
+
+        let before = Utc::now();
+        cache.set_events(vec![]);
+
+        let snapshot = cache.get();
+        assert!(snapshot.success);
+        assert!(snapshot.last_refresh.unwrap() >= before);
+    }
+
+    #[tokio::test]
+    async fn errors_keep_the_last_events() {
+        let config = Config::parse(["aws_health_exporter"]).unwrap();
+        let scraper = Scraper::with_fake_backend(&config, Arc::new(FakeBackend::default()));
+        let cache = Cache::default();
+        cache.set_events(scraper.describe_events().await.unwrap());
+        let last_refresh = cache.get().last_refresh;
+
+        cache.set_error();
+
+        let snapshot = cache.get();
+        assert!(!snapshot.success);
+        assert_eq!(snapshot.event_metrics.len(), 1);
+        assert_eq!(snapshot.last_refresh, last_refresh);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::exporter::cache::Cache;
+use crate::scraper::EventSummary;
+use chrono::{DateTime, TimeZone, Utc};
+use clap::crate_version;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+static SCHEDULED_CHANGE: &str = "scheduledChange";
+// Lines longer than this many bytes are folded, as required by RFC 5545
+const MAX_LINE_LENGTH: usize = 75;
+
+/// Handler for the calendar endpoint, serving the scheduled changes of the last refresh.
+pub async fn handle_calendar(cache: Arc<Cache>) -> StdResult<impl warp::Reply, Infallible> {
+    let snapshot = cache.get();
+    let events = snapshot
+        .event_metrics
+        .iter()
+        .flat_map(|event_metrics| &event_metrics.event_summaries);
+    Ok(warp::reply::with_header(
+        render(events, Utc::now()),
+        "content-type",
+        "text/calendar; charset=utf-8",
+    ))
+}
+
+/// Render the scheduled changes as an iCalendar feed.
+///
+/// Events are identified by their ARN, so that clients update them when their times change,
+/// the sequence number increasing with their last update.
+fn render<'a>(events: impl Iterator<Item = &'a EventSummary>, now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//aws-health-exporter//{}//EN", crate_version!()),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:AWS Health scheduled changes".to_string(),
+    ];
+    for events in scheduled_changes(events) {
+        let event = events[0];
+        // Works because only the events with an ARN and a start time are kept
+        let arn = event.arn.as_deref().unwrap();
+        let start_time = event.start_time.unwrap();
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape(arn)));
+        lines.push(format!("DTSTAMP:{}", format_time(now)));
+        if let Some(last_updated_time) = event.last_updated_time {
+            lines.push(format!("LAST-MODIFIED:{}", format_time(last_updated_time)));
+            lines.push(format!("SEQUENCE:{}", sequence(last_updated_time)));
+        }
+        lines.push(format!("DTSTART:{}", format_time(start_time)));
+        if let Some(end_time) = event.end_time {
+            lines.push(format!("DTEND:{}", format_time(end_time)));
+        }
+        lines.push(format!("SUMMARY:{}", escape(&event.event_type_code)));
+        lines.push(format!("DESCRIPTION:{}", escape(&description(&events))));
+        lines.push(format!("LOCATION:{}", escape(&event.region)));
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Scheduled changes with a start time, grouped by ARN in the order they were first seen,
+/// as an event may be retrieved from several accounts
+fn scheduled_changes<'a>(
+    events: impl Iterator<Item = &'a EventSummary>,
+) -> Vec<Vec<&'a EventSummary>> {
+    let mut indexes: HashMap<&str, usize> = HashMap::new();
+    let mut scheduled_changes: Vec<Vec<&EventSummary>> = vec![];
+    for event in events {
+        if event.event_type_category != SCHEDULED_CHANGE || event.start_time.is_none() {
+            continue;
+        }
+        let arn = match &event.arn {
+            Some(arn) => arn.as_str(),
+            None => continue,
+        };
+        match indexes.get(arn) {
+            Some(&index) => scheduled_changes[index].push(event),
+            None => {
+                indexes.insert(arn, scheduled_changes.len());
+                scheduled_changes.push(vec![event]);
+            }
+        }
+    }
+    scheduled_changes
+}
+
+/// Sequence number of an event, the seconds between the start of 2020 and its last update.
+///
+/// It only increases when the event is updated, and fits in the 32-bit integer expected by clients.
+fn sequence(last_updated_time: DateTime<Utc>) -> i64 {
+    let epoch = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+    (last_updated_time - epoch).num_seconds().max(0)
+}
+
+/// Description of an event, listing all the accounts it was retrieved from
+fn description(events: &[&EventSummary]) -> String {
+    let event = events[0];
+    let mut description = format!(
+        "Region: {}\nService: {}\nStatus: {}",
+        event.region, event.service, event.status
+    );
+    let accounts: Vec<String> = events
+        .iter()
+        .filter_map(|event| {
+            let account_id = event.account_id.as_ref()?;
+            Some(match &event.account_name {
+                Some(account_name) => format!("{} ({})", account_id, account_name),
+                None => account_id.to_owned(),
+            })
+        })
+        .collect();
+    match accounts.as_slice() {
+        [] => {}
+        [account] => description += &format!("\nAccount: {}", account),
+        accounts => description += &format!("\nAccounts: {}", accounts.join(", ")),
+    }
+    if !event.affected_accounts.is_empty() {
+        description += &format!(
+            "\nAffected accounts: {}",
+            event.affected_accounts.join(", ")
+        );
+    }
+    if let Some(event_description) = &event.description {
+        description += &format!("\n\n{}", event_description);
+    }
+    description
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a text value
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Split a long line into lines starting with a space, without splitting UTF-8 characters
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the length of the line
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(arn: &str, category: &str) -> EventSummary {
+        EventSummary {
+            arn: Some(arn.to_string()),
+            account_id: None,
+            account_name: None,
+            region: "eu-west-1".to_string(),
+            availability_zone: None,
+            service: "RDS".to_string(),
+            event_type_category: category.to_string(),
+            event_type_code: "AWS_RDS_MAINTENANCE_SCHEDULED".to_string(),
+            status: "upcoming".to_string(),
+            scope: Some("ACCOUNT_SPECIFIC".to_string()),
+            start_time: Some(Utc.ymd(2021, 2, 3).and_hms(4, 0, 0)),
+            end_time: Some(Utc.ymd(2021, 2, 3).and_hms(6, 30, 0)),
+            last_updated_time: Some(Utc.ymd(2021, 1, 20).and_hms(12, 0, 0)),
+            affected_accounts: vec![],
+            description: None,
+        }
+    }
+
+    fn vevent_lines(calendar: &str) -> Vec<&str> {
+        calendar
+            .split("\r\n")
+            .skip_while(|line| *line != "BEGIN:VEVENT")
+            .take_while(|line| *line != "END:VCALENDAR")
+            .collect()
+    }
+
+    #[test]
+    fn scheduled_changes_are_rendered() {
+        let events = [
+            event(
+                "arn:aws:health:eu-west-1::event/RDS/MAINTENANCE/1",
+                SCHEDULED_CHANGE,
+            ),
+            event("arn:aws:health:eu-west-1::event/EC2/ISSUE/2", "issue"),
+        ];
+
+        let now = Utc.ymd(2021, 1, 25).and_hms(9, 0, 0);
+        let calendar = render(events.iter(), now);
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(
+            vevent_lines(&calendar),
+            vec![
+                "BEGIN:VEVENT",
+                "UID:arn:aws:health:eu-west-1::event/RDS/MAINTENANCE/1",
+                "DTSTAMP:20210125T090000Z",
+                "LAST-MODIFIED:20210120T120000Z",
+                "SEQUENCE:33307200",
+                "DTSTART:20210203T040000Z",
+                "DTEND:20210203T063000Z",
+                "SUMMARY:AWS_RDS_MAINTENANCE_SCHEDULED",
+                "DESCRIPTION:Region: eu-west-1\\nService: RDS\\nStatus: upcoming",
+                "LOCATION:eu-west-1",
+                "END:VEVENT",
+            ]
+        );
+    }
+
+    #[test]
+    fn rescheduled_changes_keep_their_uid() {
+        let arn = "arn:aws:health:eu-west-1::event/RDS/MAINTENANCE/1";
+        let before = render([event(arn, SCHEDULED_CHANGE)].iter(), Utc::now());
+        let mut rescheduled = event(arn, SCHEDULED_CHANGE);
+        rescheduled.start_time = Some(Utc.ymd(2021, 2, 10).and_hms(4, 0, 0));
+        rescheduled.last_updated_time = Some(Utc.ymd(2021, 2, 1).and_hms(8, 0, 0));
+        let after = render([rescheduled].iter(), Utc::now());
+
+        let property = |calendar: &str, name: &str| {
+            calendar
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(str::to_string)
+                .unwrap()
+        };
+        assert_eq!(property(&before, "UID:"), property(&after, "UID:"));
+        assert_eq!(property(&after, "DTSTART:"), "20210210T040000Z");
+        let sequence = |calendar: &str| property(calendar, "SEQUENCE:").parse::<i64>().unwrap();
+        assert!(sequence(&after) > sequence(&before));
+    }
+
+    #[test]
+    fn events_of_several_accounts_are_rendered_once() {
+        let arn = "arn:aws:health:eu-west-1::event/RDS/MAINTENANCE/1";
+        let mut production = event(arn, SCHEDULED_CHANGE);
+        production.account_id = Some("111111111111".to_string());
+        production.account_name = Some("production".to_string());
+        let mut staging = event(arn, SCHEDULED_CHANGE);
+        staging.account_id = Some("222222222222".to_string());
+
+        let calendar = render([production, staging].iter(), Utc::now());
+
+        assert_eq!(calendar.matches("BEGIN:VEVENT").count(), 1);
+        let unfolded = calendar.replace("\r\n ", "");
+        assert!(unfolded.contains("\\nAccounts: 111111111111 (production)\\, 222222222222\r\n"));
+    }
+
+    #[test]
+    fn text_is_escaped_and_folded() {
+        assert_eq!(escape("a;b,c\\d\ne"), "a\\;b\\,c\\\\d\\ne");
+
+        let line = format!("DESCRIPTION:{}", "é".repeat(60));
+        let folded = fold(&line);
+        let lines: Vec<&str> = folded.split("\r\n").collect();
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+        assert!(lines[1..].iter().all(|line| line.starts_with(' ')));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
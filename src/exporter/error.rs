@@ -1,16 +1,39 @@
 use crate::scraper::error::Error as ScraperError;
 use prometheus::Error as PromError;
+use regex::Regex;
 use std::fmt;
 use std::result::Result as StdResult;
+use warp::http::StatusCode;
 
 pub type Result<T> = StdResult<T, Error>;
 
 pub enum Error {
+    InvalidConfig(clap::Error),
     ScraperError(ScraperError),
     PromError(PromError),
 }
 
-impl Error {}
+impl Error {
+    /// Status of a failed reload: only an invalid configuration is the caller's fault
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidConfig(_) => StatusCode::BAD_REQUEST,
+            Self::ScraperError(_) | Self::PromError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Message of a clap error, without the `error:` prefix, the colours and the usage
+fn plain_message(err: &clap::Error) -> String {
+    let colors = Regex::new("\x1b\\[[0-9;]*m").unwrap();
+    let message = colors.replace_all(&err.message, "");
+    let first_line = message.lines().next().unwrap_or_default();
+    first_line
+        .strip_prefix("error:")
+        .unwrap_or(first_line)
+        .trim()
+        .to_string()
+}
 
 impl From<ScraperError> for Error {
     fn from(err: ScraperError) -> Self {
@@ -27,8 +50,57 @@ impl From<PromError> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Self::InvalidConfig(err) => write!(
+                f,
+                "Invalid configuration ({:?}): {}",
+                err.kind,
+                plain_message(err)
+            ),
             Self::ScraperError(err) => write!(f, "{}", err),
             Self::PromError(err) => write!(f, "{}", err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use clap::ErrorKind;
+
+    #[test]
+    fn invalid_config_is_a_plain_message() {
+        let err = Config::parse_without_color(["aws_health_exporter", "--refresh-interval", "0"])
+            .unwrap_err();
+        let err = Error::InvalidConfig(err);
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid configuration (ValueValidation): Invalid value for '--refresh-interval \
+             <SECONDS>': must be greater than 0"
+        );
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn colours_and_usage_are_removed() {
+        // As formatted by clap when printing to a terminal
+        let err = clap::Error {
+            message: "\x1b[1;31merror:\x1b[0m `role-mfa-code` requires `role-mfa-serial`\n\n\
+                      USAGE:\n    aws_health_exporter [FLAGS] [OPTIONS]"
+                .to_string(),
+            kind: ErrorKind::MissingRequiredArgument,
+            info: None,
+        };
+        assert_eq!(
+            plain_message(&err),
+            "`role-mfa-code` requires `role-mfa-serial`"
+        );
+    }
+
+    #[test]
+    fn scraper_errors_are_server_errors() {
+        let err = Error::from(ScraperError::UnknownServices(vec!["EC3".to_string()]));
+        assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
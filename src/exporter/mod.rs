@@ -1,51 +1,91 @@
-use crate::config::{Config, TLS};
+use crate::config::{Config, Tls};
+use crate::exporter::cache::Cache;
 use crate::exporter::error::Result;
+use crate::exporter::reload::{Reloadable, Reloader};
 use crate::scraper::Scraper;
 use clap::crate_version;
-use log::warn;
+use log::{debug, warn};
 use prometheus::{
-    gather, labels, opts, register, Encoder, IntCounterVec, IntGauge, Registry, TextEncoder,
+    gather, labels, opts, proto::MetricFamily, register, Encoder, Gauge, IntCounterVec, IntGauge,
+    Registry, TextEncoder,
 };
+use std::collections::HashMap;
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::result::Result as StdResult;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep, timeout};
 use warp::Filter;
 
+mod api;
+mod cache;
+mod calendar;
 mod error;
+mod probe;
+mod reload;
 
 pub struct Exporter {
     socket_address: SocketAddr,
-    tls_config: Option<TLS>,
-    scraper: Arc<Scraper>,
+    tls_config: Option<Tls>,
+    reloader: Reloader,
+    cache: Arc<Cache>,
     exporter_metrics: Arc<IntCounterVec>,
 }
 
 impl Exporter {
-    pub fn new(config: Config) -> Result<Self> {
-        let scraper = Arc::new(Scraper::new(&config)?);
+    pub async fn new(config: Config) -> Result<Self> {
+        let reloader = Reloader::new(Reloadable::new(&config).await?);
         let exporter_metrics = Arc::new(create_exporter_metrics()?);
         create_info_metric(&config)?;
 
         Ok(Self {
             socket_address: config.socket_addr,
             tls_config: config.tls_config,
-            scraper,
+            reloader,
+            cache: Arc::new(Cache::default()),
             exporter_metrics,
         })
     }
 
     pub async fn work(&self) {
-        let scraper = self.scraper.clone();
+        tokio::spawn(refresh(self.reloader.clone(), self.cache.clone()));
+        #[cfg(unix)]
+        tokio::spawn(self.reloader.clone().reload_on_sighup());
+
+        let cache = self.cache.clone();
         let metrics_family = self.exporter_metrics.clone();
         let home = warp::path::end().map(|| warp::reply::html(HOME_PAGE.as_str()));
         let status = warp::path("status").map(|| warp::reply::html(STATUS_PAGE));
         let metrics = warp::path("metrics").and_then(move || {
-            let scraper = scraper.clone();
+            let cache = cache.clone();
             let metrics_family = metrics_family.clone();
-            scrape(scraper, metrics_family)
+            scrape(cache, metrics_family)
         });
-        let route = home.or(status).or(metrics);
+        let reloader = self.reloader.clone();
+        let reload = warp::path!("-" / "reload")
+            .and(warp::post())
+            .and_then(move || reloader.clone().handle_reload());
+        let reloader = self.reloader.clone();
+        let probe = warp::path!("probe")
+            .and(warp::query::<HashMap<String, String>>())
+            .and_then(move |params| reloader.prober().handle_probe(params));
+        let cache = self.cache.clone();
+        let events = warp::path!("api" / "v1" / "events")
+            .and(warp::get())
+            .and(warp::query::<HashMap<String, String>>())
+            .and_then(move |params| api::handle_events(cache.clone(), params));
+        let cache = self.cache.clone();
+        let calendar = warp::path!("calendar.ics")
+            .and(warp::get())
+            .and_then(move || calendar::handle_calendar(cache.clone()));
+        let route = home
+            .or(status)
+            .or(metrics)
+            .or(probe)
+            .or(events)
+            .or(calendar)
+            .or(reload);
 
         let server = warp::serve(route);
         match &self.tls_config {
@@ -61,6 +101,44 @@ impl Exporter {
     }
 }
 
+/// Periodically retrieves the events from the AWS API and stores them in the cache.
+///
+/// The delay is counted from the end of a refresh, so slow API calls don't pile up.
+/// Reloading the configuration triggers an immediate refresh.
+async fn refresh(reloader: Reloader, cache: Arc<Cache>) {
+    loop {
+        let (scraper, refresh_interval, refresh_timeout) = reloader.current();
+        refresh_events(&scraper, refresh_timeout, &cache).await;
+        tokio::select! {
+            _ = sleep(refresh_interval) => {}
+            _ = reloader.reloaded() => {}
+        }
+    }
+}
+
+/// Retrieves the events once, giving up after the refresh timeout so that a hanging call doesn't stop the refreshes.
+async fn refresh_events(scraper: &Scraper, refresh_timeout: Duration, cache: &Cache) {
+    debug!("Refreshing events...");
+    match timeout(refresh_timeout, scraper.describe_events()).await {
+        Ok(Ok(event_metrics)) => cache.set_events(event_metrics),
+        Ok(Err(err)) => {
+            warn!("{}", err);
+            cache.set_error();
+        }
+        Err(_) => {
+            warn!(
+                "Refresh didn't complete within {} seconds",
+                refresh_timeout.as_secs()
+            );
+            cache.set_error();
+        }
+    }
+    match scraper.organization_view_metrics() {
+        Ok(organization_views) => cache.set_organization_views(organization_views),
+        Err(err) => warn!("{}", err),
+    }
+}
+
 fn create_info_metric(config: &Config) -> Result<()> {
     let metric_opts = opts!(
         "aws_health_exporter_info",
@@ -85,40 +163,58 @@ fn create_exporter_metrics() -> Result<IntCounterVec> {
 }
 
 async fn scrape(
-    scraper: Arc<Scraper>,
+    cache: Arc<Cache>,
     exporter_metrics_family: Arc<IntCounterVec>,
-) -> StdResult<impl warp::Reply, Infallible> {
+) -> StdResult<String, Infallible> {
     let registry = Registry::new();
+    let snapshot = cache.get();
+
     let status_opts = opts!(
         "aws_health_events_success",
         "Whether retrieval of health events from AWS API was successful"
     );
     let status_gauge = IntGauge::with_opts(status_opts).unwrap();
+    let labels: &[&str] = if snapshot.success {
+        status_gauge.set(1);
+        &["success"]
+    } else {
+        &["error"]
+    };
+    registry.register(Box::new(status_gauge)).unwrap();
 
-    let labels: &[&str];
-    match scraper.describe_events().await {
-        Ok(event_metrics) => {
-            registry.register(Box::new(event_metrics)).unwrap();
-            status_gauge.set(1);
-            labels = &["success"];
-        }
-        Err(err) => {
-            warn!("{}", err);
-            labels = &["error"];
-        }
+    for event_metrics in snapshot.event_metrics {
+        registry.register(Box::new(event_metrics)).unwrap();
     }
-    registry.register(Box::new(status_gauge)).unwrap();
+
+    for organization_view in snapshot.organization_views {
+        registry.register(Box::new(organization_view)).unwrap();
+    }
+
+    if let Some(last_refresh) = snapshot.last_refresh {
+        let last_refresh_opts = opts!(
+            "aws_health_last_refresh_timestamp_seconds",
+            "Time of the last successful retrieval of health events from AWS API"
+        );
+        let last_refresh_gauge = Gauge::with_opts(last_refresh_opts).unwrap();
+        last_refresh_gauge.set(last_refresh.timestamp() as f64);
+        registry.register(Box::new(last_refresh_gauge)).unwrap();
+    }
+
     let exporter_metric = exporter_metrics_family
         .get_metric_with_label_values(labels)
         .unwrap();
     exporter_metric.inc();
 
-    let mut buffer = vec![];
-    let encoder = TextEncoder::new();
     let mut metric_families = gather();
     metric_families.extend(registry.gather());
-    encoder.encode(&metric_families, &mut buffer).unwrap();
-    Ok(String::from_utf8(buffer).unwrap())
+    Ok(encode(&metric_families))
+}
+
+fn encode(metric_families: &[MetricFamily]) -> String {
+    let mut buffer = vec![];
+    let encoder = TextEncoder::new();
+    encoder.encode(metric_families, &mut buffer).unwrap();
+    String::from_utf8(buffer).unwrap()
 }
 
 lazy_static! {
@@ -131,6 +227,8 @@ lazy_static! {
             <ul>
                 <li><a href=\"/status\">Exporter status</a></li>
                 <li><a href=\"/metrics\">Metrics</a></li>
+                <li><a href=\"/api/v1/events\">Events</a></li>
+                <li><a href=\"/calendar.ics\">Scheduled changes</a></li>
             </ul>
         </body>
     </html>
@@ -141,3 +239,110 @@ lazy_static! {
 
 static STATUS_PAGE: &str =
     "<html><head><title>AWS Health Exporter</title></head><body>Ok</body></html>";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scraper::fake_backend::FakeBackend;
+    use chrono::Utc;
+    use rusoto_health::{DescribeEventsResponse, Event};
+
+    static REFRESH_TIMEOUT: Duration = Duration::from_secs(300);
+
+    fn scraper(backend: Arc<FakeBackend>) -> Scraper {
+        let config = Config::parse(["aws_health_exporter"]).unwrap();
+        Scraper::with_fake_backend(&config, backend)
+    }
+
+    fn page(code: &str) -> DescribeEventsResponse {
+        DescribeEventsResponse {
+            events: Some(vec![Event {
+                arn: Some(format!("arn:aws:health:us-east-1::event/EC2/{}/1", code)),
+                event_type_category: Some("issue".into()),
+                event_type_code: Some(code.into()),
+                region: Some("us-east-1".into()),
+                service: Some("EC2".into()),
+                status_code: Some("open".into()),
+                ..Default::default()
+            }]),
+            next_token: None,
+        }
+    }
+
+    async fn get_metrics(cache: &Arc<Cache>) -> String {
+        let exporter_metrics = IntCounterVec::new(opts!("http_requests", "help"), &["status"]);
+        scrape(cache.clone(), Arc::new(exporter_metrics.unwrap()))
+            .await
+            .unwrap()
+    }
+
+    /// Value of the first sample of the metric
+    fn value(metrics: &str, name: &str) -> Option<f64> {
+        metrics
+            .lines()
+            .filter(|line| line.starts_with(&format!("{} ", name)))
+            .find_map(|line| line.rsplit(' ').next()?.parse().ok())
+    }
+
+    #[tokio::test]
+    async fn metrics_are_served_from_the_last_refresh() {
+        let backend = Arc::new(FakeBackend::default());
+        backend.events.push_page(page("AWS_EC2_OPERATIONAL_ISSUE"));
+        let cache = Arc::new(Cache::default());
+
+        let before = Utc::now().timestamp() as f64;
+        refresh_events(&scraper(backend.clone()), REFRESH_TIMEOUT, &cache).await;
+        let after = Utc::now().timestamp() as f64;
+
+        for _ in 0..2 {
+            let metrics = get_metrics(&cache).await;
+            assert!(metrics.contains("event_type_code=\"AWS_EC2_OPERATIONAL_ISSUE\""));
+            assert_eq!(value(&metrics, "aws_health_events_success"), Some(1.0));
+            let last_refresh =
+                value(&metrics, "aws_health_last_refresh_timestamp_seconds").unwrap();
+            assert!(before <= last_refresh && last_refresh <= after);
+        }
+        assert_eq!(backend.events.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn nothing_is_served_before_the_first_refresh() {
+        let metrics = get_metrics(&Arc::new(Cache::default())).await;
+
+        assert_eq!(value(&metrics, "aws_health_events_success"), Some(0.0));
+        assert_eq!(
+            value(&metrics, "aws_health_last_refresh_timestamp_seconds"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn hanging_refreshes_time_out_and_keep_the_last_events() {
+        tokio::time::pause();
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_page(page("AWS_EC2_OPERATIONAL_ISSUE"))
+            .push_hang()
+            .push_page(page("AWS_EC2_INSTANCE_RETIREMENT_SCHEDULED"));
+        let scraper = scraper(backend.clone());
+        let cache = Arc::new(Cache::default());
+        refresh_events(&scraper, REFRESH_TIMEOUT, &cache).await;
+        let last_refresh = cache.get().last_refresh;
+
+        refresh_events(&scraper, REFRESH_TIMEOUT, &cache).await;
+
+        assert_eq!(cache.get().last_refresh, last_refresh);
+        let metrics = get_metrics(&cache).await;
+        assert_eq!(value(&metrics, "aws_health_events_success"), Some(0.0));
+        assert!(metrics.contains("event_type_code=\"AWS_EC2_OPERATIONAL_ISSUE\""));
+
+        // The hanging call is abandoned rather than joined by the next refresh
+        refresh_events(&scraper, REFRESH_TIMEOUT, &cache).await;
+
+        let metrics = get_metrics(&cache).await;
+        assert_eq!(value(&metrics, "aws_health_events_success"), Some(1.0));
+        assert!(metrics.contains("event_type_code=\"AWS_EC2_INSTANCE_RETIREMENT_SCHEDULED\""));
+        assert_eq!(backend.events.requests().len(), 3);
+    }
+}
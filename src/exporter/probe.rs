@@ -0,0 +1,148 @@
+use crate::config::{validate_role_arn, Config};
+use crate::exporter::encode;
+use crate::scraper::error::Result as ScraperResult;
+use crate::scraper::Scraper;
+use log::warn;
+use prometheus::{opts, IntGauge, Registry};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+use warp::http::StatusCode;
+
+/// Queries a single account per request, chosen by the `role` URL parameter.
+///
+/// Only the roles of `--probe-role` and `--account-role` are accepted, so that callers can't make
+/// the exporter assume arbitrary roles. Clients are kept around so that the credentials for a role
+/// are only refreshed when they expire, the allowed roles bounding their number.
+pub struct Prober {
+    config: Config,
+    scrapers: Mutex<HashMap<String, Arc<Scraper>>>,
+}
+
+impl Prober {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            scrapers: Mutex::default(),
+        }
+    }
+
+    fn is_allowed(&self, role: &str) -> bool {
+        self.config
+            .probe_roles
+            .iter()
+            .any(|allowed| allowed == role)
+            || self
+                .config
+                .account_roles
+                .iter()
+                .flatten()
+                .any(|account_role| account_role.role == role)
+    }
+
+    fn scraper(&self, role: &str) -> ScraperResult<Arc<Scraper>> {
+        let mut scrapers = self.scrapers.lock().unwrap();
+        if let Some(scraper) = scrapers.get(role) {
+            return Ok(scraper.clone());
+        }
+        let scraper = Arc::new(Scraper::for_role(&self.config, role)?);
+        scrapers.insert(role.to_string(), scraper.clone());
+        Ok(scraper)
+    }
+
+    /// Retrieve the events of the account right away, failures are reflected by the success metric.
+    async fn probe(&self, role: &str) -> String {
+        let registry = Registry::new();
+        let success_opts = opts!(
+            "aws_health_events_success",
+            "Whether retrieval of health events from AWS API was successful"
+        );
+        let success_gauge = IntGauge::with_opts(success_opts).unwrap();
+
+        let result = match self.scraper(role) {
+            Ok(scraper) => scraper.describe_events().await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(all_event_metrics) => {
+                success_gauge.set(1);
+                for event_metrics in all_event_metrics {
+                    registry.register(Box::new(event_metrics)).unwrap();
+                }
+            }
+            Err(err) => warn!("Failed to probe {}: {}", role, err),
+        }
+        registry.register(Box::new(success_gauge)).unwrap();
+
+        encode(&registry.gather())
+    }
+
+    /// Handler for the probe endpoint
+    pub async fn handle_probe(
+        self: Arc<Self>,
+        params: HashMap<String, String>,
+    ) -> StdResult<impl warp::Reply, Infallible> {
+        let role = match params.get("role") {
+            Some(role) => role,
+            None => {
+                return Ok(warp::reply::with_status(
+                    "Missing `role` parameter".to_string(),
+                    StatusCode::BAD_REQUEST,
+                ))
+            }
+        };
+        let valid_role =
+            validate_role_arn(role.to_owned()).and_then(|_| self.config.partition.check_role(role));
+        if let Err(err) = valid_role {
+            return Ok(warp::reply::with_status(
+                format!("Invalid `role` parameter: {}", err),
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+        if !self.is_allowed(role) {
+            return Ok(warp::reply::with_status(
+                format!(
+                    "Role {} is not allowed, it must be given with `--probe-role` or `--account-role`",
+                    role
+                ),
+                StatusCode::FORBIDDEN,
+            ));
+        }
+        Ok(warp::reply::with_status(
+            self.probe(role).await,
+            StatusCode::OK,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PROBE_ROLE: &str = "arn:aws:iam::111111111111:role/aws-health-exporter";
+    static ACCOUNT_ROLE: &str = "arn:aws:iam::222222222222:role/aws-health-exporter";
+
+    #[test]
+    fn only_configured_roles_are_allowed() {
+        let args = [
+            "aws_health_exporter",
+            "--probe-role",
+            PROBE_ROLE,
+            "--account-role",
+            ACCOUNT_ROLE,
+        ];
+        let prober = Prober::new(Config::parse(args).unwrap());
+
+        assert!(prober.is_allowed(PROBE_ROLE));
+        assert!(prober.is_allowed(ACCOUNT_ROLE));
+        assert!(!prober.is_allowed("arn:aws:iam::333333333333:role/aws-health-exporter"));
+    }
+
+    #[test]
+    fn no_role_is_allowed_by_default() {
+        let prober = Prober::new(Config::parse(["aws_health_exporter"]).unwrap());
+
+        assert!(!prober.is_allowed(PROBE_ROLE));
+    }
+}
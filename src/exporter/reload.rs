@@ -0,0 +1,205 @@
+use crate::config::Config;
+use crate::exporter::error::{Error, Result};
+use crate::exporter::probe::Prober;
+use crate::scraper::{check_profile, Scraper};
+use log::{info, warn};
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::result::Result as StdResult;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+use warp::http::StatusCode;
+
+/// Part of the exporter which is rebuilt when the configuration is reloaded.
+///
+/// The listen address and TLS configuration are only read at startup.
+pub struct Reloadable {
+    pub scraper: Arc<Scraper>,
+    pub refresh_interval: Duration,
+    pub refresh_timeout: Duration,
+    /// Forgets the clients of the probed roles on reload
+    pub prober: Arc<Prober>,
+}
+
+impl Reloadable {
+    pub async fn new(config: &Config) -> Result<Self> {
+        check_profile(config).await?;
+        let scraper = Scraper::new(config)?;
+        if config.event_types {
+            scraper.validate_services().await?;
+        }
+        Ok(Self {
+            scraper: Arc::new(scraper),
+            refresh_interval: config.refresh_interval,
+            refresh_timeout: config.refresh_timeout,
+            prober: Arc::new(Prober::new(config.clone())),
+        })
+    }
+}
+
+/// Shares the reloadable state and wakes up the refresh task when it changes
+#[derive(Clone)]
+pub struct Reloader {
+    reloadable: Arc<RwLock<Reloadable>>,
+    reloaded: Arc<Notify>,
+}
+
+impl Reloader {
+    pub fn new(reloadable: Reloadable) -> Self {
+        Self {
+            reloadable: Arc::new(RwLock::new(reloadable)),
+            reloaded: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Current scraper, along with the refresh interval and timeout
+    pub fn current(&self) -> (Arc<Scraper>, Duration, Duration) {
+        let reloadable = self.reloadable.read().unwrap();
+        (
+            reloadable.scraper.clone(),
+            reloadable.refresh_interval,
+            reloadable.refresh_timeout,
+        )
+    }
+
+    pub fn prober(&self) -> Arc<Prober> {
+        self.reloadable.read().unwrap().prober.clone()
+    }
+
+    /// Resolves when the configuration was reloaded since the last call
+    pub async fn reloaded(&self) {
+        self.reloaded.notified().await
+    }
+
+    /// Read the configuration again, from the same arguments as at startup.
+    ///
+    /// The new state is only swapped in once it's completely built,
+    /// so an invalid configuration leaves the current one active.
+    pub async fn reload(&self) -> Result<()> {
+        self.reload_from(std::env::args_os().collect()).await
+    }
+
+    async fn reload_from(&self, args: Vec<OsString>) -> Result<()> {
+        let config = Config::parse_without_color(args).map_err(Error::InvalidConfig)?;
+        let reloadable = Reloadable::new(&config).await?;
+        *self.reloadable.write().unwrap() = reloadable;
+        self.reloaded.notify_one();
+        info!("Configuration reloaded.");
+        Ok(())
+    }
+
+    async fn reload_or_warn(&self) -> Result<()> {
+        let result = self.reload().await;
+        if let Err(err) = &result {
+            warn!(
+                "Failed to reload configuration, keeping the current one: {}",
+                err
+            );
+        }
+        result
+    }
+
+    /// Reload the configuration each time the process receives SIGHUP
+    #[cfg(unix)]
+    pub async fn reload_on_sighup(self) {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sighup = match signal(SignalKind::hangup()) {
+            Ok(sighup) => sighup,
+            Err(err) => {
+                warn!("Failed to listen for SIGHUP: {}", err);
+                return;
+            }
+        };
+        while sighup.recv().await.is_some() {
+            info!("Got SIGHUP, reloading configuration...");
+            let _ = self.reload_or_warn().await;
+        }
+    }
+
+    /// Handler for the reload endpoint
+    pub async fn handle_reload(self) -> StdResult<impl warp::Reply, Infallible> {
+        let reply = match self.reload_or_warn().await {
+            Ok(()) => warp::reply::with_status("Ok".to_string(), StatusCode::OK),
+            Err(err) => warp::reply::with_status(err.to_string(), err.status_code()),
+        };
+        Ok(reply)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use tokio::time::timeout;
+
+    /// Configuration file only holding the given refresh interval
+    fn config_file(name: &str, refresh_interval: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "aws_health_exporter-{}-{}.toml",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, format!("refresh-interval = {}\n", refresh_interval)).unwrap();
+        path
+    }
+
+    fn args(config_file: &Path) -> Vec<OsString> {
+        let args = [
+            "aws_health_exporter",
+            "--config",
+            config_file.to_str().unwrap(),
+        ];
+        args.iter().map(OsString::from).collect()
+    }
+
+    async fn reloader(config_file: &Path) -> Reloader {
+        let config = Config::parse(args(config_file)).unwrap();
+        match Reloadable::new(&config).await {
+            Ok(reloadable) => Reloader::new(reloadable),
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn valid_reloads_swap_the_scraper() {
+        let path = config_file("valid-reload", "60");
+        let reloader = reloader(&path).await;
+        let (scraper, refresh_interval, _) = reloader.current();
+        assert_eq!(refresh_interval, Duration::from_secs(60));
+
+        std::fs::write(&path, "refresh-interval = 120\n").unwrap();
+        let result = reloader.reload_from(args(&path)).await;
+
+        std::fs::remove_file(path).unwrap();
+        assert!(result.is_ok());
+        let (reloaded_scraper, refresh_interval, _) = reloader.current();
+        assert_eq!(refresh_interval, Duration::from_secs(120));
+        assert!(!Arc::ptr_eq(&scraper, &reloaded_scraper));
+        let notified = timeout(Duration::from_secs(1), reloader.reloaded()).await;
+        assert!(notified.is_ok());
+    }
+
+    #[tokio::test]
+    async fn invalid_reloads_keep_the_current_scraper() {
+        let path = config_file("invalid-reload", "60");
+        let reloader = reloader(&path).await;
+        let (scraper, _, _) = reloader.current();
+
+        std::fs::write(&path, "refresh-interval = \"soon\"\n").unwrap();
+        let result = reloader.reload_from(args(&path)).await;
+
+        std::fs::remove_file(path).unwrap();
+        let err = match result {
+            Ok(()) => panic!("The invalid configuration was applied"),
+            Err(err) => err,
+        };
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        let (current_scraper, refresh_interval, _) = reloader.current();
+        assert_eq!(refresh_interval, Duration::from_secs(60));
+        assert!(Arc::ptr_eq(&scraper, &current_scraper));
+        let notified = timeout(Duration::from_millis(100), reloader.reloaded()).await;
+        assert!(notified.is_err());
+    }
+}
@@ -18,7 +18,7 @@ async fn main() {
         config.version, config.socket_addr
     );
 
-    match Exporter::new(config) {
+    match Exporter::new(config).await {
         Ok(exporter) => exporter.work().await,
         Err(err) => error!("Failed to create exporter: {}", err),
     }
@@ -0,0 +1,53 @@
+use rusoto_health::{
+    DescribeAffectedAccountsForOrganizationRequest, DescribeAffectedAccountsForOrganizationResponse,
+};
+
+use super::error::Result;
+use super::pagination::{paginate, PaginatedRequest, PaginatedResponse};
+use super::{HealthEvent, ScraperInner};
+
+impl ScraperInner {
+    /// Retrieve the accounts affected by each of the given organization events.
+    pub(super) async fn describe_affected_accounts(
+        &self,
+        events: &mut [HealthEvent],
+    ) -> Result<()> {
+        for event in events {
+            let event_arn = match &event.arn {
+                Some(event_arn) => event_arn.to_owned(),
+                None => continue,
+            };
+            let request = DescribeAffectedAccountsForOrganizationRequest {
+                event_arn,
+                ..Default::default()
+            };
+            let mut account_ids = vec![];
+            paginate(
+                request,
+                |request| {
+                    self.backend
+                        .describe_affected_accounts_for_organization(request)
+                },
+                |response| {
+                    account_ids.extend(response.affected_accounts.unwrap_or_default());
+                    Ok(())
+                },
+            )
+            .await?;
+            event.account_ids = account_ids;
+        }
+        Ok(())
+    }
+}
+
+impl PaginatedRequest for DescribeAffectedAccountsForOrganizationRequest {
+    fn set_next_token(&mut self, next_token: Option<String>) {
+        self.next_token = next_token;
+    }
+}
+
+impl PaginatedResponse for DescribeAffectedAccountsForOrganizationResponse {
+    fn get_next_token(&self) -> Option<String> {
+        self.next_token.clone()
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use prometheus::{opts, IntGaugeVec};
+use rusoto_health::{DescribeEventAggregatesRequest, DescribeEventAggregatesResponse, EventFilter};
+
+use super::error::Result;
+use super::pagination::{paginate, PaginatedRequest, PaginatedResponse};
+use super::ScraperInner;
+
+pub(super) fn new_event_count_metrics(
+    const_labels: HashMap<String, String>,
+) -> Result<IntGaugeVec> {
+    let opts = opts!(
+        "aws_health_event_count",
+        "Number of AWS Health events, aggregated by a given field"
+    )
+    .const_labels(const_labels);
+    let labels = ["aggregate_field", "aggregate_value"];
+    Ok(IntGaugeVec::new(opts, &labels)?)
+}
+
+impl ScraperInner {
+    /// Retrieve the number of events matching the filters, aggregated by the given field.
+    pub(super) async fn describe_event_aggregates(
+        &self,
+        aggregate_field: &str,
+        metric_family: &IntGaugeVec,
+    ) -> Result<()> {
+        let request = DescribeEventAggregatesRequest {
+            aggregate_field: aggregate_field.to_owned(),
+            filter: Some(EventFilter::from(self.generic_filter())),
+            ..Default::default()
+        };
+        paginate(
+            request,
+            |request| self.backend.describe_event_aggregates(request),
+            |response| {
+                for aggregate in response.event_aggregates.iter().flatten() {
+                    let aggregate_value = aggregate.aggregate_value.as_deref().unwrap_or("");
+                    let metric = metric_family
+                        .get_metric_with_label_values(&[aggregate_field, aggregate_value])?;
+                    metric.set(aggregate.count.unwrap_or_default());
+                }
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+
+impl PaginatedRequest for DescribeEventAggregatesRequest {
+    fn set_next_token(&mut self, next_token: Option<String>) {
+        self.next_token = next_token;
+    }
+}
+
+impl PaginatedResponse for DescribeEventAggregatesResponse {
+    fn get_next_token(&self) -> Option<String> {
+        self.next_token.clone()
+    }
+}
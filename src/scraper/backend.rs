@@ -0,0 +1,372 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rusoto_core::proto::json::ResponsePayload;
+use rusoto_core::request::{BufferedHttpResponse, HttpDispatchError};
+use rusoto_core::signature::SignedRequest;
+use rusoto_core::{Client, Region, RusotoError, RusotoResult};
+use rusoto_health::{
+    AWSHealth, AWSHealthClient, DescribeAffectedAccountsForOrganizationError,
+    DescribeAffectedAccountsForOrganizationRequest,
+    DescribeAffectedAccountsForOrganizationResponse, DescribeAffectedEntitiesError,
+    DescribeAffectedEntitiesForOrganizationError, DescribeAffectedEntitiesForOrganizationRequest,
+    DescribeAffectedEntitiesForOrganizationResponse, DescribeAffectedEntitiesRequest,
+    DescribeAffectedEntitiesResponse, DescribeEventAggregatesError, DescribeEventAggregatesRequest,
+    DescribeEventAggregatesResponse, DescribeEventDetailsError,
+    DescribeEventDetailsForOrganizationError, DescribeEventDetailsForOrganizationRequest,
+    DescribeEventDetailsRequest, DescribeEventTypesError, DescribeEventTypesRequest,
+    DescribeEventsError, DescribeEventsForOrganizationError, DescribeEventsForOrganizationRequest,
+    DescribeEventsForOrganizationResponse, DescribeEventsRequest, DescribeEventsResponse,
+    DescribeHealthServiceStatusForOrganizationError,
+    DescribeHealthServiceStatusForOrganizationResponse,
+};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::time::timeout;
+
+use super::details::{DescribeEventDetailsForOrganizationResponse, DescribeEventDetailsResponse};
+use super::event_types::DescribeEventTypesResponse;
+
+/// AWS Health operations used by the exporter.
+///
+/// Implemented by the AWS API, and by a fake in tests.
+#[async_trait]
+pub(super) trait HealthBackend: Send + Sync {
+    async fn describe_events(
+        &self,
+        input: DescribeEventsRequest,
+    ) -> RusotoResult<DescribeEventsResponse, DescribeEventsError>;
+
+    async fn describe_events_for_organization(
+        &self,
+        input: DescribeEventsForOrganizationRequest,
+    ) -> RusotoResult<DescribeEventsForOrganizationResponse, DescribeEventsForOrganizationError>;
+
+    async fn describe_affected_accounts_for_organization(
+        &self,
+        input: DescribeAffectedAccountsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedAccountsForOrganizationError,
+    >;
+
+    async fn describe_affected_entities(
+        &self,
+        input: DescribeAffectedEntitiesRequest,
+    ) -> RusotoResult<DescribeAffectedEntitiesResponse, DescribeAffectedEntitiesError>;
+
+    async fn describe_affected_entities_for_organization(
+        &self,
+        input: DescribeAffectedEntitiesForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedEntitiesForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationError,
+    >;
+
+    async fn describe_event_aggregates(
+        &self,
+        input: DescribeEventAggregatesRequest,
+    ) -> RusotoResult<DescribeEventAggregatesResponse, DescribeEventAggregatesError>;
+
+    async fn describe_event_details(
+        &self,
+        input: DescribeEventDetailsRequest,
+    ) -> RusotoResult<DescribeEventDetailsResponse, DescribeEventDetailsError>;
+
+    async fn describe_event_details_for_organization(
+        &self,
+        input: DescribeEventDetailsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeEventDetailsForOrganizationResponse,
+        DescribeEventDetailsForOrganizationError,
+    >;
+
+    async fn describe_event_types(
+        &self,
+        input: DescribeEventTypesRequest,
+    ) -> RusotoResult<DescribeEventTypesResponse, DescribeEventTypesError>;
+
+    async fn describe_health_service_status_for_organization(
+        &self,
+    ) -> RusotoResult<
+        DescribeHealthServiceStatusForOrganizationResponse,
+        DescribeHealthServiceStatusForOrganizationError,
+    >;
+}
+
+/// Calls the AWS API of the given region
+pub(super) struct RusotoBackend {
+    client: AWSHealthClient,
+    /// Used for the calls which can't go through the generated client
+    core_client: Client,
+    region: Region,
+}
+
+impl RusotoBackend {
+    pub fn new(core_client: Client, region: Region) -> Self {
+        Self {
+            client: AWSHealthClient::new_with_client(core_client.clone(), region.clone()),
+            core_client,
+            region,
+        }
+    }
+
+    /// Same as the calls of the generated `AWSHealthClient`, but with the given response type.
+    async fn call_with_response<Req, Resp, E>(
+        &self,
+        operation: &str,
+        input: &Req,
+        from_response: fn(BufferedHttpResponse) -> RusotoError<E>,
+    ) -> RusotoResult<Resp, E>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let mut request = SignedRequest::new("POST", "health", &self.region, "/");
+        request.set_content_type("application/x-amz-json-1.1".to_owned());
+        request.add_header("x-amz-target", &format!("AWSHealth_20160804.{}", operation));
+        request.set_payload(Some(serde_json::to_string(input).unwrap()));
+
+        let mut response = self.core_client.sign_and_dispatch(request).await?;
+        let response = response.buffer().await.map_err(RusotoError::HttpDispatch)?;
+        if !response.status.is_success() {
+            return Err(from_response(response));
+        }
+        ResponsePayload::new(&response).deserialize::<Resp, _>()
+    }
+}
+
+#[async_trait]
+impl HealthBackend for RusotoBackend {
+    async fn describe_events(
+        &self,
+        input: DescribeEventsRequest,
+    ) -> RusotoResult<DescribeEventsResponse, DescribeEventsError> {
+        self.client.describe_events(input).await
+    }
+
+    async fn describe_events_for_organization(
+        &self,
+        input: DescribeEventsForOrganizationRequest,
+    ) -> RusotoResult<DescribeEventsForOrganizationResponse, DescribeEventsForOrganizationError>
+    {
+        self.client.describe_events_for_organization(input).await
+    }
+
+    async fn describe_affected_accounts_for_organization(
+        &self,
+        input: DescribeAffectedAccountsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedAccountsForOrganizationError,
+    > {
+        self.client
+            .describe_affected_accounts_for_organization(input)
+            .await
+    }
+
+    async fn describe_affected_entities(
+        &self,
+        input: DescribeAffectedEntitiesRequest,
+    ) -> RusotoResult<DescribeAffectedEntitiesResponse, DescribeAffectedEntitiesError> {
+        self.client.describe_affected_entities(input).await
+    }
+
+    async fn describe_affected_entities_for_organization(
+        &self,
+        input: DescribeAffectedEntitiesForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedEntitiesForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationError,
+    > {
+        self.client
+            .describe_affected_entities_for_organization(input)
+            .await
+    }
+
+    async fn describe_event_aggregates(
+        &self,
+        input: DescribeEventAggregatesRequest,
+    ) -> RusotoResult<DescribeEventAggregatesResponse, DescribeEventAggregatesError> {
+        self.client.describe_event_aggregates(input).await
+    }
+
+    async fn describe_event_details(
+        &self,
+        input: DescribeEventDetailsRequest,
+    ) -> RusotoResult<DescribeEventDetailsResponse, DescribeEventDetailsError> {
+        self.call_with_response(
+            "DescribeEventDetails",
+            &input,
+            DescribeEventDetailsError::from_response,
+        )
+        .await
+    }
+
+    async fn describe_event_details_for_organization(
+        &self,
+        input: DescribeEventDetailsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeEventDetailsForOrganizationResponse,
+        DescribeEventDetailsForOrganizationError,
+    > {
+        self.call_with_response(
+            "DescribeEventDetailsForOrganization",
+            &input,
+            DescribeEventDetailsForOrganizationError::from_response,
+        )
+        .await
+    }
+
+    async fn describe_event_types(
+        &self,
+        input: DescribeEventTypesRequest,
+    ) -> RusotoResult<DescribeEventTypesResponse, DescribeEventTypesError> {
+        self.call_with_response(
+            "DescribeEventTypes",
+            &input,
+            DescribeEventTypesError::from_response,
+        )
+        .await
+    }
+
+    async fn describe_health_service_status_for_organization(
+        &self,
+    ) -> RusotoResult<
+        DescribeHealthServiceStatusForOrganizationResponse,
+        DescribeHealthServiceStatusForOrganizationError,
+    > {
+        self.client
+            .describe_health_service_status_for_organization()
+            .await
+    }
+}
+
+/// Fails the calls to another backend which aren't answered in time.
+///
+/// The HTTP client has no timeout of its own, so a hanging endpoint would otherwise block the refreshes.
+/// Calls which time out fail like unreachable endpoints, so that the passive endpoint is tried.
+pub(super) struct TimeoutBackend {
+    inner: Arc<dyn HealthBackend>,
+    timeout: Duration,
+}
+
+impl TimeoutBackend {
+    pub fn new(inner: Arc<dyn HealthBackend>, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    async fn within<T, E>(
+        &self,
+        call: impl Future<Output = RusotoResult<T, E>>,
+    ) -> RusotoResult<T, E> {
+        match timeout(self.timeout, call).await {
+            Ok(result) => result,
+            Err(_) => Err(RusotoError::HttpDispatch(HttpDispatchError::new(format!(
+                "No response from the AWS Health endpoint within {} seconds",
+                self.timeout.as_secs()
+            )))),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthBackend for TimeoutBackend {
+    async fn describe_events(
+        &self,
+        input: DescribeEventsRequest,
+    ) -> RusotoResult<DescribeEventsResponse, DescribeEventsError> {
+        self.within(self.inner.describe_events(input)).await
+    }
+
+    async fn describe_events_for_organization(
+        &self,
+        input: DescribeEventsForOrganizationRequest,
+    ) -> RusotoResult<DescribeEventsForOrganizationResponse, DescribeEventsForOrganizationError>
+    {
+        self.within(self.inner.describe_events_for_organization(input))
+            .await
+    }
+
+    async fn describe_affected_accounts_for_organization(
+        &self,
+        input: DescribeAffectedAccountsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedAccountsForOrganizationError,
+    > {
+        self.within(
+            self.inner
+                .describe_affected_accounts_for_organization(input),
+        )
+        .await
+    }
+
+    async fn describe_affected_entities(
+        &self,
+        input: DescribeAffectedEntitiesRequest,
+    ) -> RusotoResult<DescribeAffectedEntitiesResponse, DescribeAffectedEntitiesError> {
+        self.within(self.inner.describe_affected_entities(input))
+            .await
+    }
+
+    async fn describe_affected_entities_for_organization(
+        &self,
+        input: DescribeAffectedEntitiesForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedEntitiesForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationError,
+    > {
+        self.within(
+            self.inner
+                .describe_affected_entities_for_organization(input),
+        )
+        .await
+    }
+
+    async fn describe_event_aggregates(
+        &self,
+        input: DescribeEventAggregatesRequest,
+    ) -> RusotoResult<DescribeEventAggregatesResponse, DescribeEventAggregatesError> {
+        self.within(self.inner.describe_event_aggregates(input))
+            .await
+    }
+
+    async fn describe_event_details(
+        &self,
+        input: DescribeEventDetailsRequest,
+    ) -> RusotoResult<DescribeEventDetailsResponse, DescribeEventDetailsError> {
+        self.within(self.inner.describe_event_details(input)).await
+    }
+
+    async fn describe_event_details_for_organization(
+        &self,
+        input: DescribeEventDetailsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeEventDetailsForOrganizationResponse,
+        DescribeEventDetailsForOrganizationError,
+    > {
+        self.within(self.inner.describe_event_details_for_organization(input))
+            .await
+    }
+
+    async fn describe_event_types(
+        &self,
+        input: DescribeEventTypesRequest,
+    ) -> RusotoResult<DescribeEventTypesResponse, DescribeEventTypesError> {
+        self.within(self.inner.describe_event_types(input)).await
+    }
+
+    async fn describe_health_service_status_for_organization(
+        &self,
+    ) -> RusotoResult<
+        DescribeHealthServiceStatusForOrganizationResponse,
+        DescribeHealthServiceStatusForOrganizationError,
+    > {
+        self.within(self.inner.describe_health_service_status_for_organization())
+            .await
+    }
+}
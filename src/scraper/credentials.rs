@@ -0,0 +1,392 @@
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+use rusoto_core::{Client, HttpClient, Region};
+use rusoto_credential::{
+    AutoRefreshingProvider, AwsCredentials, CredentialsError, DefaultCredentialsProvider,
+    ProfileProvider, ProvideAwsCredentials, Secret, Variable,
+};
+use rusoto_sts::{
+    AssumeRoleWithWebIdentityRequest, NewAwsCredsForStsCreds, Sts,
+    StsAssumeRoleSessionCredentialsProvider, StsClient,
+};
+
+use super::custom_endpoint;
+use super::error::Result;
+use crate::config::{Config, Partition};
+
+/// Credentials used to call the API, or to assume the role calling it
+enum BaseCredentials {
+    Default(DefaultCredentialsProvider),
+    WebIdentity(AutoRefreshingProvider<WebIdentitySession>),
+    Profile(AutoRefreshingProvider<ProfileProvider>),
+}
+
+impl BaseCredentials {
+    fn new(config: &Config) -> Result<Self> {
+        if let Some(profile_provider) = profile_provider(config)? {
+            return Ok(Self::Profile(AutoRefreshingProvider::new(
+                profile_provider,
+            )?));
+        }
+        match WebIdentitySession::new(config)? {
+            Some(session) => Ok(Self::WebIdentity(AutoRefreshingProvider::new(session)?)),
+            None => Ok(Self::Default(DefaultCredentialsProvider::new()?)),
+        }
+    }
+}
+
+#[async_trait]
+impl ProvideAwsCredentials for BaseCredentials {
+    async fn credentials(&self) -> std::result::Result<AwsCredentials, CredentialsError> {
+        match self {
+            Self::Default(provider) => provider.credentials().await,
+            Self::WebIdentity(provider) => provider.credentials().await,
+            Self::Profile(provider) => provider.credentials().await,
+        }
+    }
+}
+
+/// Session of the web identity role.
+///
+/// Unlike rusoto's `WebIdentityProvider`, which always calls the public STS endpoint of the default region,
+/// STS is called with the same region and endpoint as when assuming the other roles.
+struct WebIdentitySession {
+    sts: StsClient,
+    /// Read again on every refresh, as Kubernetes rotates it
+    token: Variable<Secret, CredentialsError>,
+    role: String,
+    session_name: String,
+    duration: Option<i64>,
+    policy: Option<String>,
+}
+
+impl WebIdentitySession {
+    fn new(config: &Config) -> Result<Option<Self>> {
+        let web_identity = match &config.web_identity {
+            Some(web_identity) => web_identity,
+            None => return Ok(None),
+        };
+        // The web identity token is the only proof of identity, so the call isn't signed
+        let client = Client::new_not_signing(HttpClient::new()?);
+        let options = &config.assume_role_options;
+        Ok(Some(Self {
+            sts: StsClient::new_with_client(client, sts_region(config)?),
+            token: Variable::from_text_file(&web_identity.token_file),
+            role: web_identity.role.to_owned(),
+            session_name: options.session_name.to_owned(),
+            duration: options.duration.map(|duration| duration.as_secs() as i64),
+            policy: options.policy.to_owned(),
+        }))
+    }
+}
+
+#[async_trait]
+impl ProvideAwsCredentials for WebIdentitySession {
+    async fn credentials(&self) -> std::result::Result<AwsCredentials, CredentialsError> {
+        let request = AssumeRoleWithWebIdentityRequest {
+            role_arn: self.role.to_owned(),
+            role_session_name: self.session_name.to_owned(),
+            web_identity_token: self.token.resolve()?.as_ref().to_owned(),
+            duration_seconds: self.duration,
+            policy: self.policy.to_owned(),
+            ..Default::default()
+        };
+        let response = self
+            .sts
+            .assume_role_with_web_identity(request)
+            .await
+            .map_err(CredentialsError::new)?;
+        match response.credentials {
+            Some(credentials) => AwsCredentials::new_for_credentials(credentials),
+            None => Err(CredentialsError::new(
+                "No credentials in the AssumeRoleWithWebIdentity response",
+            )),
+        }
+    }
+}
+
+/// Session of a role requiring MFA, started with a code which can only be used once.
+///
+/// Renewing the session once it expires sends the same code again, which AWS refuses with a vague error.
+struct MfaSession<P> {
+    /// Renews the session when it expires
+    provider: P,
+    role: String,
+    started: AtomicBool,
+}
+
+impl<P> MfaSession<P> {
+    fn new(provider: P, role: &str) -> Self {
+        Self {
+            provider,
+            role: role.to_owned(),
+            started: AtomicBool::new(false),
+        }
+    }
+}
+
+#[async_trait]
+impl<P: ProvideAwsCredentials + Send + Sync> ProvideAwsCredentials for MfaSession<P> {
+    async fn credentials(&self) -> std::result::Result<AwsCredentials, CredentialsError> {
+        match self.provider.credentials().await {
+            Ok(credentials) => {
+                self.started.store(true, Ordering::Relaxed);
+                Ok(credentials)
+            }
+            Err(err) if self.started.load(Ordering::Relaxed) => {
+                let message = format!(
+                    "The session of role {} expired and can't be renewed with the same MFA code, \
+                     update `role-mfa-code` and reload the configuration: {}",
+                    self.role, err
+                );
+                Err(CredentialsError::new(message))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Provider for the profile and credentials file from the configuration, if any
+fn profile_provider(config: &Config) -> Result<Option<ProfileProvider>> {
+    let provider = match (&config.credentials_file, &config.profile) {
+        (Some(file_path), Some(profile)) => ProfileProvider::with_configuration(file_path, profile),
+        (Some(file_path), None) => ProfileProvider::with_default_configuration(file_path),
+        (None, Some(profile)) => ProfileProvider::with_default_credentials(profile)?,
+        (None, None) => return Ok(None),
+    };
+    Ok(Some(provider))
+}
+
+/// Check that the profile from the configuration, if any, provides credentials.
+///
+/// Otherwise, the error would only show up when calling the API.
+pub(crate) async fn check_profile(config: &Config) -> Result<()> {
+    let provider = match profile_provider(config)? {
+        Some(provider) => provider,
+        None => return Ok(()),
+    };
+    provider.credentials().await.map_err(|err| {
+        CredentialsError::new(format!(
+            "Failed to load profile `{}` from {}: {}",
+            provider.profile(),
+            provider.file_path().display(),
+            err
+        ))
+    })?;
+    Ok(())
+}
+
+/// Whether the default credentials are used, so that the shared client can sign the requests
+fn uses_default_credentials(config: &Config) -> Result<bool> {
+    Ok(config.web_identity.is_none() && profile_provider(config)?.is_none())
+}
+
+/// Region and endpoint of STS, used to assume all the roles
+fn sts_region(config: &Config) -> Result<Region> {
+    let region = match &config.role_region {
+        Some(region) => Region::from_str(region)?,
+        // The default region may be in another partition
+        None => match Region::default() {
+            region if Partition::of_region(region.name()) == config.partition => region,
+            _ => config.partition.health_region(),
+        },
+    };
+    Ok(custom_endpoint(region, config.sts_endpoint.as_deref()))
+}
+
+/// Create a client signing requests with the base credentials, or with those of the given role.
+///
+/// Documentation related to handling assumed roles:
+/// https://github.com/rusoto/rusoto/blob/master/AWS-CREDENTIALS.md
+pub(super) fn new_client(config: &Config, role: Option<&str>) -> Result<Client> {
+    let role = match role {
+        None if uses_default_credentials(config)? => return Ok(Client::shared()),
+        None => {
+            return Ok(Client::new_with(
+                BaseCredentials::new(config)?,
+                HttpClient::new()?,
+            ))
+        }
+        Some(role) => role,
+    };
+
+    let sts = StsClient::new_with(
+        HttpClient::new()?,
+        BaseCredentials::new(config)?,
+        sts_region(config)?,
+    );
+
+    let options = &config.assume_role_options;
+    let mut sts_provider = StsAssumeRoleSessionCredentialsProvider::new(
+        sts,
+        role.to_owned(),
+        options.session_name.to_owned(),
+        options.external_id.to_owned(),
+        options
+            .duration
+            .map(|duration| chrono::Duration::seconds(duration.as_secs() as i64)),
+        options.policy.to_owned(),
+        options.mfa_serial.to_owned(),
+    );
+    let mfa_code = options.mfa_code.as_ref();
+    if let Some(mfa_code) = mfa_code {
+        sts_provider.set_mfa_code(mfa_code);
+    }
+    let auto_refreshing_provider = AutoRefreshingProvider::new(sts_provider)?;
+    if mfa_code.is_some() {
+        let mfa_session = MfaSession::new(auto_refreshing_provider, role);
+        return Ok(Client::new_with(mfa_session, HttpClient::new()?));
+    }
+    Ok(Client::new_with(
+        auto_refreshing_provider,
+        HttpClient::new()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use warp::Filter;
+
+    fn config(args: &[&str]) -> Config {
+        Config::parse(["aws_health_exporter"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn profile_is_used_without_role() {
+        let config = config(&[
+            "--credentials-file",
+            "tests/fixtures/credentials",
+            "--profile",
+            "health",
+        ]);
+        assert!(!uses_default_credentials(&config).unwrap());
+    }
+
+    #[test]
+    fn shared_client_uses_default_credentials() {
+        assert!(uses_default_credentials(&config(&[])).unwrap());
+    }
+
+    /// Answers with each of the results in turn, like a session renewed with an MFA code used once
+    struct ScriptedProvider(Mutex<Vec<std::result::Result<AwsCredentials, String>>>);
+
+    #[async_trait]
+    impl ProvideAwsCredentials for ScriptedProvider {
+        async fn credentials(&self) -> std::result::Result<AwsCredentials, CredentialsError> {
+            self.0
+                .lock()
+                .unwrap()
+                .remove(0)
+                .map_err(CredentialsError::new)
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_mfa_sessions_ask_for_a_new_code() {
+        let role = "arn:aws:iam::123456789012:role/aws-health-exporter";
+        let provider = ScriptedProvider(Mutex::new(vec![
+            Ok(AwsCredentials::default()),
+            Err("MultiFactorAuthentication failed with invalid MFA one time pass code".into()),
+        ]));
+        let mfa_session = MfaSession::new(provider, role);
+
+        assert!(mfa_session.credentials().await.is_ok());
+        let err = mfa_session.credentials().await.unwrap_err();
+
+        assert!(err.message.contains(&format!(
+            "The session of role {} expired and can't be renewed with the same MFA code",
+            role
+        )));
+        assert!(err.message.contains("invalid MFA one time pass code"));
+    }
+
+    #[tokio::test]
+    async fn first_mfa_failures_are_kept() {
+        let provider = ScriptedProvider(Mutex::new(vec![Err("Access denied".into())]));
+        let mfa_session = MfaSession::new(provider, "arn:aws:iam::123456789012:role/r");
+
+        let err = mfa_session.credentials().await.unwrap_err();
+
+        assert_eq!(err.message, "Access denied");
+    }
+
+    type Forms = Arc<Mutex<Vec<HashMap<String, String>>>>;
+
+    /// Stand-in for STS, answering AssumeRoleWithWebIdentity and keeping the form of each request
+    fn sts_endpoint() -> (String, Forms) {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+        let route =
+            warp::post()
+                .and(warp::body::form())
+                .map(move |form: HashMap<String, String>| {
+                    received.lock().unwrap().push(form);
+                    "<AssumeRoleWithWebIdentityResponse>\
+                   <AssumeRoleWithWebIdentityResult><Credentials>\
+                     <AccessKeyId>ASIAWEBIDENTITY</AccessKeyId>\
+                     <SecretAccessKey>secret</SecretAccessKey>\
+                     <SessionToken>session-token</SessionToken>\
+                     <Expiration>2100-01-01T00:00:00Z</Expiration>\
+                   </Credentials></AssumeRoleWithWebIdentityResult>\
+                   <ResponseMetadata><RequestId>1</RequestId></ResponseMetadata>\
+                 </AssumeRoleWithWebIdentityResponse>"
+                });
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{}", address), requests)
+    }
+
+    #[tokio::test]
+    async fn web_identity_uses_the_sts_endpoint() {
+        let (endpoint, requests) = sts_endpoint();
+        let role = "arn:aws-cn:iam::123456789012:role/aws-health-exporter";
+        let config = config(&[
+            "--partition",
+            "aws-cn",
+            "--web-identity",
+            "--web-identity-token-file",
+            "tests/fixtures/web-identity-token",
+            "--web-identity-role",
+            role,
+            "--sts-endpoint",
+            &endpoint,
+        ]);
+
+        let session = WebIdentitySession::new(&config).unwrap().unwrap();
+        let credentials = session.credentials().await.unwrap();
+
+        assert_eq!(credentials.aws_access_key_id(), "ASIAWEBIDENTITY");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["Action"], "AssumeRoleWithWebIdentity");
+        assert_eq!(requests[0]["RoleArn"], role);
+        assert_eq!(
+            requests[0]["WebIdentityToken"],
+            "eyJhbGciOiJSUzI1NiJ9.web-identity-token"
+        );
+    }
+
+    #[test]
+    fn sts_is_called_in_the_partition() {
+        let config = config(&[
+            "--partition",
+            "aws-us-gov",
+            "--role",
+            "arn:aws-us-gov:iam::123456789012:role/aws-health-exporter",
+            "--sts-endpoint",
+            "https://sts.example.com",
+        ]);
+        match sts_region(&config).unwrap() {
+            Region::Custom { name, endpoint } => {
+                assert_eq!(Partition::of_region(&name), Partition::AwsUsGov);
+                assert_eq!(endpoint, "https://sts.example.com");
+            }
+            region => panic!("Unexpected region {:?}", region),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use log::warn;
+use prometheus::{opts, IntGauge, IntGaugeVec};
+use rusoto_health::{
+    DescribeEventDetailsForOrganizationRequest, DescribeEventDetailsRequest, Event,
+    EventAccountFilter, EventDetailsErrorItem, OrganizationEventDetailsErrorItem,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::error::Result;
+use super::pagination::retry_throttled;
+use super::{HealthEvent, ScraperInner};
+
+// Maximum number of events accepted by a single DescribeEventDetails call
+const EVENTS_PER_REQUEST: usize = 10;
+// Descriptions are cut to this many characters in the labels of the metric
+const DESCRIPTION_LABEL_LENGTH: usize = 256;
+// Hex digits kept from the hash of the full description
+const DESCRIPTION_HASH_LENGTH: usize = 16;
+
+pub(super) fn new_event_description_metrics(
+    const_labels: HashMap<String, String>,
+) -> Result<IntGaugeVec> {
+    let opts = opts!(
+        "aws_health_event_description_info",
+        "Latest description of open AWS Health events, truncated"
+    )
+    .const_labels(const_labels);
+    let labels = [
+        "event_arn",
+        "event_type_code",
+        "region",
+        "service",
+        "description",
+        "description_hash",
+    ];
+    Ok(IntGaugeVec::new(opts, &labels)?)
+}
+
+pub(super) fn new_event_details_failure_metric(
+    const_labels: HashMap<String, String>,
+) -> Result<IntGauge> {
+    let opts = opts!(
+        "aws_health_event_details_failures",
+        "Open events whose details couldn't be retrieved during the last refresh, \
+         counted once per affected account for organization events"
+    )
+    .const_labels(const_labels);
+    Ok(IntGauge::with_opts(opts)?)
+}
+
+/// Event details as returned by DescribeEventDetails and DescribeEventDetailsForOrganization.
+///
+/// rusoto_health models the description as a plain string instead of a structure,
+/// so the generated client fails to deserialize the response.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct EventDetails {
+    pub event: Option<Event>,
+    pub event_description: Option<EventDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct EventDescription {
+    pub latest_description: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DescribeEventDetailsResponse {
+    pub successful_set: Option<Vec<EventDetails>>,
+    pub failed_set: Option<Vec<EventDetailsErrorItem>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DescribeEventDetailsForOrganizationResponse {
+    pub successful_set: Option<Vec<EventDetails>>,
+    pub failed_set: Option<Vec<OrganizationEventDetailsErrorItem>>,
+}
+
+impl ScraperInner {
+    /// Retrieve the latest description of the open events.
+    ///
+    /// Events whose details fail are logged and skipped, their number is returned.
+    /// Account-specific organization events are queried, and their failures counted, for each affected account.
+    pub(super) async fn describe_event_details(&self, events: &mut [HealthEvent]) -> Result<i64> {
+        let mut descriptions = HashMap::new();
+        let mut failures = 0;
+
+        if self.use_organization {
+            let filters: Vec<EventAccountFilter> = events
+                .iter()
+                .filter(|event| event.is_open())
+                .filter_map(|event| event.arn.as_ref().map(|event_arn| (event, event_arn)))
+                .flat_map(|(event, event_arn)| {
+                    let account_ids = if event.is_public() {
+                        vec![None]
+                    } else {
+                        event.account_ids.iter().cloned().map(Some).collect()
+                    };
+                    account_ids
+                        .into_iter()
+                        .map(move |aws_account_id| EventAccountFilter {
+                            aws_account_id,
+                            event_arn: event_arn.to_owned(),
+                        })
+                })
+                .collect();
+
+            for filters in filters.chunks(EVENTS_PER_REQUEST) {
+                let request = DescribeEventDetailsForOrganizationRequest {
+                    locale: self.locale.to_owned(),
+                    organization_event_detail_filters: filters.to_vec(),
+                };
+                let response = retry_throttled(&request, &|request| {
+                    self.backend
+                        .describe_event_details_for_organization(request)
+                })
+                .await?;
+                for item in response.failed_set.iter().flatten() {
+                    warn!(
+                        "Failed to retrieve details of event {} in account {}: {}",
+                        item.event_arn.as_deref().unwrap_or(""),
+                        item.aws_account_id.as_deref().unwrap_or(""),
+                        item.error_message.as_deref().unwrap_or(""),
+                    );
+                    failures += 1;
+                }
+                handle_details(response.successful_set, &mut descriptions);
+            }
+        } else {
+            let event_arns: Vec<String> = events
+                .iter()
+                .filter(|event| event.is_open())
+                .filter_map(|event| event.arn.to_owned())
+                .collect();
+
+            for event_arns in event_arns.chunks(EVENTS_PER_REQUEST) {
+                let request = DescribeEventDetailsRequest {
+                    event_arns: event_arns.to_vec(),
+                    locale: self.locale.to_owned(),
+                };
+                let response = retry_throttled(&request, &|request| {
+                    self.backend.describe_event_details(request)
+                })
+                .await?;
+                for item in response.failed_set.iter().flatten() {
+                    warn!(
+                        "Failed to retrieve details of event {}: {}",
+                        item.event_arn.as_deref().unwrap_or(""),
+                        item.error_message.as_deref().unwrap_or(""),
+                    );
+                    failures += 1;
+                }
+                handle_details(response.successful_set, &mut descriptions);
+            }
+        }
+
+        for event in events {
+            if let Some(event_arn) = &event.arn {
+                event.description = descriptions.get(event_arn).cloned();
+            }
+        }
+        Ok(failures)
+    }
+}
+
+/// Keep the first description of each event, those of other accounts being the same
+fn handle_details(details: Option<Vec<EventDetails>>, descriptions: &mut HashMap<String, String>) {
+    for details in details.into_iter().flatten() {
+        let event_arn = details.event.and_then(|event| event.arn);
+        let description = details
+            .event_description
+            .and_then(|description| description.latest_description);
+        if let (Some(event_arn), Some(description)) = (event_arn, description) {
+            descriptions.entry(event_arn).or_insert(description);
+        }
+    }
+}
+
+/// Set one series per event with a description, labelled with its beginning and a hash of the whole.
+pub(super) fn set_description_metrics(
+    events: &[HealthEvent],
+    metric_family: &IntGaugeVec,
+) -> Result<()> {
+    for event in events {
+        let (event_arn, description) = match (&event.arn, &event.description) {
+            (Some(event_arn), Some(description)) => (event_arn, description),
+            _ => continue,
+        };
+        let truncated: String = description.chars().take(DESCRIPTION_LABEL_LENGTH).collect();
+        let hash = format!("{:x}", Sha256::digest(description.as_bytes()));
+        let metric = metric_family.get_metric_with_label_values(&[
+            event_arn,
+            &event.fields["event_type_code"],
+            &event.fields["region"],
+            &event.fields["service"],
+            &truncated,
+            &hash[..DESCRIPTION_HASH_LENGTH],
+        ])?;
+        metric.set(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use log::warn;
+use prometheus::{opts, IntGaugeVec};
+use rusoto_health::{
+    AffectedEntity, DescribeAffectedEntitiesForOrganizationRequest,
+    DescribeAffectedEntitiesForOrganizationResponse, DescribeAffectedEntitiesRequest,
+    DescribeAffectedEntitiesResponse, EntityFilter, EventAccountFilter,
+};
+
+use super::error::Result;
+use super::pagination::{paginate, PaginatedRequest, PaginatedResponse};
+use super::{HealthEvent, ScraperInner};
+
+// Maximum number of filters accepted by a single DescribeAffectedEntities call
+const EVENTS_PER_REQUEST: usize = 10;
+
+pub(super) fn new_affected_entity_metrics(
+    const_labels: HashMap<String, String>,
+) -> Result<IntGaugeVec> {
+    let opts = opts!(
+        "aws_health_affected_entity",
+        "Entities affected by open AWS Health events"
+    )
+    .const_labels(const_labels);
+    let labels = ["event_type_code", "entity_value", "entity_arn", "status"];
+    Ok(IntGaugeVec::new(opts, &labels)?)
+}
+
+impl ScraperInner {
+    /// Retrieve the entities affected by the given events.
+    ///
+    /// For organization events whose affected accounts are known, entities are queried per account.
+    pub(super) async fn describe_affected_entities(
+        &self,
+        events: &[&HealthEvent],
+        metric_family: &IntGaugeVec,
+    ) -> Result<()> {
+        let event_type_codes: HashMap<&str, &str> = events
+            .iter()
+            .filter_map(|event| {
+                let event_arn = event.arn.as_deref()?;
+                Some((event_arn, event.fields["event_type_code"].as_str()))
+            })
+            .collect();
+
+        if self.use_organization {
+            let filters: Vec<EventAccountFilter> = events
+                .iter()
+                .filter_map(|event| event.arn.as_ref().map(|event_arn| (event, event_arn)))
+                .flat_map(|(event, event_arn)| {
+                    let account_ids = match event.account_ids.as_slice() {
+                        [] => vec![None],
+                        account_ids => account_ids.iter().cloned().map(Some).collect(),
+                    };
+                    account_ids
+                        .into_iter()
+                        .map(move |aws_account_id| EventAccountFilter {
+                            aws_account_id,
+                            event_arn: event_arn.to_owned(),
+                        })
+                })
+                .collect();
+
+            for filters in filters.chunks(EVENTS_PER_REQUEST) {
+                let request = DescribeAffectedEntitiesForOrganizationRequest {
+                    locale: self.locale.to_owned(),
+                    organization_entity_filters: filters.to_vec(),
+                    ..Default::default()
+                };
+                paginate(
+                    request,
+                    |request| {
+                        self.backend
+                            .describe_affected_entities_for_organization(request)
+                    },
+                    |response| {
+                        log_failed_set(&response);
+                        handle_entities(&response.entities, &event_type_codes, metric_family)
+                    },
+                )
+                .await?;
+            }
+        } else {
+            let event_arns: Vec<String> = event_type_codes
+                .keys()
+                .map(|event_arn| event_arn.to_string())
+                .collect();
+
+            for event_arns in event_arns.chunks(EVENTS_PER_REQUEST) {
+                let request = DescribeAffectedEntitiesRequest {
+                    filter: EntityFilter {
+                        event_arns: event_arns.to_vec(),
+                        ..Default::default()
+                    },
+                    locale: self.locale.to_owned(),
+                    ..Default::default()
+                };
+                paginate(
+                    request,
+                    |request| self.backend.describe_affected_entities(request),
+                    |response| {
+                        handle_entities(&response.entities, &event_type_codes, metric_family)
+                    },
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn handle_entities(
+    entities: &Option<Vec<AffectedEntity>>,
+    event_type_codes: &HashMap<&str, &str>,
+    metric_family: &IntGaugeVec,
+) -> Result<()> {
+    for entity in entities.iter().flatten() {
+        let event_type_code = entity
+            .event_arn
+            .as_deref()
+            .and_then(|event_arn| event_type_codes.get(event_arn))
+            .unwrap_or(&"");
+        let entity_value = entity.entity_value.as_deref().unwrap_or("");
+        let entity_arn = entity.entity_arn.as_deref().unwrap_or("");
+        let status = entity.status_code.as_deref().unwrap_or("");
+
+        let metric = metric_family.get_metric_with_label_values(&[
+            event_type_code,
+            entity_value,
+            entity_arn,
+            status,
+        ])?;
+        metric.set(1);
+    }
+    Ok(())
+}
+
+fn log_failed_set(response: &DescribeAffectedEntitiesForOrganizationResponse) {
+    for item in response.failed_set.iter().flatten() {
+        warn!(
+            "Failed to retrieve affected entities for event {} in account {}: {}",
+            item.event_arn.as_deref().unwrap_or(""),
+            item.aws_account_id.as_deref().unwrap_or(""),
+            item.error_message.as_deref().unwrap_or(""),
+        );
+    }
+}
+
+impl PaginatedRequest for DescribeAffectedEntitiesRequest {
+    fn set_next_token(&mut self, next_token: Option<String>) {
+        self.next_token = next_token;
+    }
+}
+
+impl PaginatedRequest for DescribeAffectedEntitiesForOrganizationRequest {
+    fn set_next_token(&mut self, next_token: Option<String>) {
+        self.next_token = next_token;
+    }
+}
+
+impl PaginatedResponse for DescribeAffectedEntitiesResponse {
+    fn get_next_token(&self) -> Option<String> {
+        self.next_token.clone()
+    }
+}
+
+impl PaginatedResponse for DescribeAffectedEntitiesForOrganizationResponse {
+    fn get_next_token(&self) -> Option<String> {
+        self.next_token.clone()
+    }
+}
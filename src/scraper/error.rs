@@ -1,21 +1,73 @@
 use prometheus::Error as PromError;
 use rusoto_core::{request::TlsError, RusotoError};
 use rusoto_credential::CredentialsError;
-use rusoto_health::{DescribeEventsError, DescribeEventsForOrganizationError};
+use rusoto_health::{
+    DescribeAffectedAccountsForOrganizationError, DescribeAffectedEntitiesError,
+    DescribeAffectedEntitiesForOrganizationError, DescribeEventAggregatesError,
+    DescribeEventDetailsError, DescribeEventDetailsForOrganizationError, DescribeEventTypesError,
+    DescribeEventsError, DescribeEventsForOrganizationError,
+    DescribeHealthServiceStatusForOrganizationError,
+};
 use rusoto_signature::region::ParseRegionError;
-use std::{fmt, result::Result as StdResult};
+use std::{fmt, result::Result as StdResult, sync::Arc};
 
 pub type Result<T> = StdResult<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
-    DescribeEventsError(RusotoError<DescribeEventsError>),
-    DescribeEventsForOrganizationError(RusotoError<DescribeEventsForOrganizationError>),
+    DescribeEvents(Box<RusotoError<DescribeEventsError>>),
+    DescribeEventsForOrganization(Box<RusotoError<DescribeEventsForOrganizationError>>),
+    DescribeAffectedAccountsForOrganization(
+        Box<RusotoError<DescribeAffectedAccountsForOrganizationError>>,
+    ),
+    DescribeAffectedEntities(Box<RusotoError<DescribeAffectedEntitiesError>>),
+    DescribeAffectedEntitiesForOrganization(
+        Box<RusotoError<DescribeAffectedEntitiesForOrganizationError>>,
+    ),
+    DescribeEventAggregates(Box<RusotoError<DescribeEventAggregatesError>>),
+    DescribeEventDetails(Box<RusotoError<DescribeEventDetailsError>>),
+    DescribeEventDetailsForOrganization(Box<RusotoError<DescribeEventDetailsForOrganizationError>>),
+    DescribeEventTypes(Box<RusotoError<DescribeEventTypesError>>),
+    DescribeHealthServiceStatusForOrganization(
+        Box<RusotoError<DescribeHealthServiceStatusForOrganizationError>>,
+    ),
     InvalidRegion(ParseRegionError),
     InvalidCredentials(CredentialsError),
     TlsError(TlsError),
     PromError(PromError),
     TooManyRetries,
+    UnknownServices(Vec<String>),
+    /// Status of the organizational view, when it isn't enabled
+    OrganizationViewDisabled(String),
+    /// Error of a call shared by several callers
+    Shared(Arc<Error>),
+}
+
+impl Error {
+    /// Whether the error comes from the AWS Health endpoint being unreachable or failing
+    pub fn is_endpoint_failure(&self) -> bool {
+        match self {
+            Self::DescribeEvents(err) => is_endpoint_failure(err),
+            Self::DescribeEventsForOrganization(err) => is_endpoint_failure(err),
+            Self::DescribeAffectedAccountsForOrganization(err) => is_endpoint_failure(err),
+            Self::DescribeAffectedEntities(err) => is_endpoint_failure(err),
+            Self::DescribeAffectedEntitiesForOrganization(err) => is_endpoint_failure(err),
+            Self::DescribeEventAggregates(err) => is_endpoint_failure(err),
+            Self::DescribeEventDetails(err) => is_endpoint_failure(err),
+            Self::DescribeEventDetailsForOrganization(err) => is_endpoint_failure(err),
+            Self::DescribeEventTypes(err) => is_endpoint_failure(err),
+            Self::DescribeHealthServiceStatusForOrganization(err) => is_endpoint_failure(err),
+            _ => false,
+        }
+    }
+}
+
+fn is_endpoint_failure<E>(err: &RusotoError<E>) -> bool {
+    match err {
+        RusotoError::HttpDispatch(_) => true,
+        RusotoError::Unknown(response) => response.status.is_server_error(),
+        _ => false,
+    }
 }
 
 impl From<ParseRegionError> for Error {
@@ -26,13 +78,61 @@ impl From<ParseRegionError> for Error {
 
 impl From<RusotoError<DescribeEventsError>> for Error {
     fn from(err: RusotoError<DescribeEventsError>) -> Self {
-        Self::DescribeEventsError(err)
+        Self::DescribeEvents(Box::new(err))
     }
 }
 
 impl From<RusotoError<DescribeEventsForOrganizationError>> for Error {
     fn from(err: RusotoError<DescribeEventsForOrganizationError>) -> Self {
-        Self::DescribeEventsForOrganizationError(err)
+        Self::DescribeEventsForOrganization(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeAffectedAccountsForOrganizationError>> for Error {
+    fn from(err: RusotoError<DescribeAffectedAccountsForOrganizationError>) -> Self {
+        Self::DescribeAffectedAccountsForOrganization(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeAffectedEntitiesError>> for Error {
+    fn from(err: RusotoError<DescribeAffectedEntitiesError>) -> Self {
+        Self::DescribeAffectedEntities(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeAffectedEntitiesForOrganizationError>> for Error {
+    fn from(err: RusotoError<DescribeAffectedEntitiesForOrganizationError>) -> Self {
+        Self::DescribeAffectedEntitiesForOrganization(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeEventAggregatesError>> for Error {
+    fn from(err: RusotoError<DescribeEventAggregatesError>) -> Self {
+        Self::DescribeEventAggregates(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeEventDetailsError>> for Error {
+    fn from(err: RusotoError<DescribeEventDetailsError>) -> Self {
+        Self::DescribeEventDetails(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeEventDetailsForOrganizationError>> for Error {
+    fn from(err: RusotoError<DescribeEventDetailsForOrganizationError>) -> Self {
+        Self::DescribeEventDetailsForOrganization(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeEventTypesError>> for Error {
+    fn from(err: RusotoError<DescribeEventTypesError>) -> Self {
+        Self::DescribeEventTypes(Box::new(err))
+    }
+}
+
+impl From<RusotoError<DescribeHealthServiceStatusForOrganizationError>> for Error {
+    fn from(err: RusotoError<DescribeHealthServiceStatusForOrganizationError>) -> Self {
+        Self::DescribeHealthServiceStatusForOrganization(Box::new(err))
     }
 }
 
@@ -57,13 +157,31 @@ impl From<PromError> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::DescribeEventsError(err) => write!(f, "{}", err),
-            Self::DescribeEventsForOrganizationError(err) => write!(f, "{}", err),
+            Self::DescribeEvents(err) => write!(f, "{}", err),
+            Self::DescribeEventsForOrganization(err) => write!(f, "{}", err),
+            Self::DescribeAffectedAccountsForOrganization(err) => write!(f, "{}", err),
+            Self::DescribeAffectedEntities(err) => write!(f, "{}", err),
+            Self::DescribeAffectedEntitiesForOrganization(err) => write!(f, "{}", err),
+            Self::DescribeEventAggregates(err) => write!(f, "{}", err),
+            Self::DescribeEventDetails(err) => write!(f, "{}", err),
+            Self::DescribeEventDetailsForOrganization(err) => write!(f, "{}", err),
+            Self::DescribeEventTypes(err) => write!(f, "{}", err),
+            Self::DescribeHealthServiceStatusForOrganization(err) => write!(f, "{}", err),
             Self::InvalidRegion(err) => write!(f, "{}", err),
             Self::PromError(err) => write!(f, "{}", err),
             Self::TlsError(err) => write!(f, "{}", err),
             Self::InvalidCredentials(err) => write!(f, "{}", err),
             Self::TooManyRetries => write!(f, "API call was throttled too many times."),
+            Self::UnknownServices(services) => {
+                write!(f, "Unknown services: {}", services.join(", "))
+            }
+            Self::OrganizationViewDisabled(status) => write!(
+                f,
+                "The organizational view of AWS Health isn't enabled (status: {}). \
+                 Enable it from the management account to query organization events.",
+                status
+            ),
+            Self::Shared(err) => write!(f, "{}", err),
         }
     }
 }
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use prometheus::{opts, IntGaugeVec};
+use rusoto_health::{DescribeEventTypesRequest, EventTypeFilter};
+use serde::Deserialize;
+
+use super::error::Result;
+use super::pagination::{paginate, PaginatedRequest, PaginatedResponse};
+use super::ScraperInner;
+
+pub(super) fn new_event_type_metrics(const_labels: HashMap<String, String>) -> Result<IntGaugeVec> {
+    let opts = opts!(
+        "aws_health_event_type_info",
+        "Event types which may apply to the selected services"
+    )
+    .const_labels(const_labels);
+    let labels = ["service", "code", "category"];
+    Ok(IntGaugeVec::new(opts, &labels)?)
+}
+
+/// Event type as returned by DescribeEventTypes.
+///
+/// rusoto_health models the event types as plain strings instead of structures,
+/// so the generated client fails to deserialize the response.
+#[derive(Deserialize)]
+pub(super) struct EventType {
+    pub service: Option<String>,
+    pub code: Option<String>,
+    pub category: Option<String>,
+}
+
+impl EventType {
+    pub(super) fn set_metric(&self, metric_family: &IntGaugeVec) -> Result<()> {
+        let service = self.service.as_deref().unwrap_or("");
+        let code = self.code.as_deref().unwrap_or("");
+        let category = self.category.as_deref().unwrap_or("");
+        let metric = metric_family.get_metric_with_label_values(&[service, code, category])?;
+        metric.set(1);
+        Ok(())
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DescribeEventTypesResponse {
+    pub event_types: Option<Vec<EventType>>,
+    pub next_token: Option<String>,
+}
+
+impl ScraperInner {
+    /// Retrieve the event types which apply to the selected services.
+    pub(super) async fn describe_event_types(&self) -> Result<Vec<EventType>> {
+        let request = DescribeEventTypesRequest {
+            filter: Some(EventTypeFilter {
+                services: self.services.to_owned(),
+                ..Default::default()
+            }),
+            locale: self.locale.to_owned(),
+            ..Default::default()
+        };
+        let mut event_types = vec![];
+        paginate(
+            request,
+            |request| self.backend.describe_event_types(request),
+            |response| {
+                event_types.extend(response.event_types.unwrap_or_default());
+                Ok(())
+            },
+        )
+        .await?;
+        Ok(event_types)
+    }
+}
+
+impl PaginatedRequest for DescribeEventTypesRequest {
+    fn set_next_token(&mut self, next_token: Option<String>) {
+        self.next_token = next_token;
+    }
+}
+
+impl PaginatedResponse for DescribeEventTypesResponse {
+    fn get_next_token(&self) -> Option<String> {
+        self.next_token.clone()
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use futures::future::pending;
+use rusoto_core::request::BufferedHttpResponse;
+use rusoto_core::{RusotoError, RusotoResult};
+use rusoto_health::{
+    DescribeAffectedAccountsForOrganizationError, DescribeAffectedAccountsForOrganizationRequest,
+    DescribeAffectedAccountsForOrganizationResponse, DescribeAffectedEntitiesError,
+    DescribeAffectedEntitiesForOrganizationError, DescribeAffectedEntitiesForOrganizationRequest,
+    DescribeAffectedEntitiesForOrganizationResponse, DescribeAffectedEntitiesRequest,
+    DescribeAffectedEntitiesResponse, DescribeEventAggregatesError, DescribeEventAggregatesRequest,
+    DescribeEventAggregatesResponse, DescribeEventDetailsError,
+    DescribeEventDetailsForOrganizationError, DescribeEventDetailsForOrganizationRequest,
+    DescribeEventDetailsRequest, DescribeEventTypesError, DescribeEventTypesRequest,
+    DescribeEventsError, DescribeEventsForOrganizationError, DescribeEventsForOrganizationRequest,
+    DescribeEventsForOrganizationResponse, DescribeEventsRequest, DescribeEventsResponse,
+    DescribeHealthServiceStatusForOrganizationError,
+    DescribeHealthServiceStatusForOrganizationResponse,
+};
+use warp::http::StatusCode;
+
+use super::backend::HealthBackend;
+use super::details::{DescribeEventDetailsForOrganizationResponse, DescribeEventDetailsResponse};
+use super::event_types::DescribeEventTypesResponse;
+
+/// Scripted responses of a single operation, along with the requests it received.
+///
+/// Once the script runs out, the operation answers with an empty last page.
+pub(crate) struct Script<Req, Resp, E> {
+    /// No response stands for an endpoint which never answers
+    responses: Mutex<VecDeque<Option<RusotoResult<Resp, E>>>>,
+    requests: Mutex<Vec<Req>>,
+}
+
+impl<Req, Resp, E> Default for Script<Req, Resp, E> {
+    fn default() -> Self {
+        Self {
+            responses: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(vec![]),
+        }
+    }
+}
+
+impl<Req: Clone, Resp: Default, E> Script<Req, Resp, E> {
+    pub fn push_page(&self, response: Resp) -> &Self {
+        self.responses.lock().unwrap().push_back(Some(Ok(response)));
+        self
+    }
+
+    pub fn push_error(&self, err: RusotoError<E>) -> &Self {
+        self.responses.lock().unwrap().push_back(Some(Err(err)));
+        self
+    }
+
+    /// Answer with a TooManyRequests status, the way the API throttles calls.
+    pub fn push_throttling(&self) -> &Self {
+        self.push_error(RusotoError::Unknown(BufferedHttpResponse {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            body: Default::default(),
+            headers: Default::default(),
+        }))
+    }
+
+    /// Never answer, like an endpoint which accepted the connection but hangs.
+    pub fn push_hang(&self) -> &Self {
+        self.responses.lock().unwrap().push_back(None);
+        self
+    }
+
+    pub fn requests(&self) -> Vec<Req> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    // Same result type as the rusoto calls
+    #[allow(clippy::result_large_err)]
+    async fn respond(&self, request: Req) -> RusotoResult<Resp, E> {
+        self.requests.lock().unwrap().push(request);
+        let response = self.responses.lock().unwrap().pop_front();
+        match response {
+            Some(Some(response)) => response,
+            Some(None) => pending().await,
+            None => Ok(Resp::default()),
+        }
+    }
+}
+
+/// In-memory AWS Health API
+#[derive(Default)]
+pub(crate) struct FakeBackend {
+    pub events: Script<DescribeEventsRequest, DescribeEventsResponse, DescribeEventsError>,
+    pub events_for_organization: Script<
+        DescribeEventsForOrganizationRequest,
+        DescribeEventsForOrganizationResponse,
+        DescribeEventsForOrganizationError,
+    >,
+    pub affected_accounts_for_organization: Script<
+        DescribeAffectedAccountsForOrganizationRequest,
+        DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedAccountsForOrganizationError,
+    >,
+    pub affected_entities: Script<
+        DescribeAffectedEntitiesRequest,
+        DescribeAffectedEntitiesResponse,
+        DescribeAffectedEntitiesError,
+    >,
+    pub affected_entities_for_organization: Script<
+        DescribeAffectedEntitiesForOrganizationRequest,
+        DescribeAffectedEntitiesForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationError,
+    >,
+    pub event_aggregates: Script<
+        DescribeEventAggregatesRequest,
+        DescribeEventAggregatesResponse,
+        DescribeEventAggregatesError,
+    >,
+    pub(super) event_details: Script<
+        DescribeEventDetailsRequest,
+        DescribeEventDetailsResponse,
+        DescribeEventDetailsError,
+    >,
+    pub(super) event_details_for_organization: Script<
+        DescribeEventDetailsForOrganizationRequest,
+        DescribeEventDetailsForOrganizationResponse,
+        DescribeEventDetailsForOrganizationError,
+    >,
+    pub(super) event_types:
+        Script<DescribeEventTypesRequest, DescribeEventTypesResponse, DescribeEventTypesError>,
+    pub health_service_status_for_organization: Script<
+        (),
+        DescribeHealthServiceStatusForOrganizationResponse,
+        DescribeHealthServiceStatusForOrganizationError,
+    >,
+}
+
+#[async_trait]
+impl HealthBackend for FakeBackend {
+    async fn describe_events(
+        &self,
+        input: DescribeEventsRequest,
+    ) -> RusotoResult<DescribeEventsResponse, DescribeEventsError> {
+        self.events.respond(input).await
+    }
+
+    async fn describe_events_for_organization(
+        &self,
+        input: DescribeEventsForOrganizationRequest,
+    ) -> RusotoResult<DescribeEventsForOrganizationResponse, DescribeEventsForOrganizationError>
+    {
+        self.events_for_organization.respond(input).await
+    }
+
+    async fn describe_affected_accounts_for_organization(
+        &self,
+        input: DescribeAffectedAccountsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedAccountsForOrganizationError,
+    > {
+        self.affected_accounts_for_organization.respond(input).await
+    }
+
+    async fn describe_affected_entities(
+        &self,
+        input: DescribeAffectedEntitiesRequest,
+    ) -> RusotoResult<DescribeAffectedEntitiesResponse, DescribeAffectedEntitiesError> {
+        self.affected_entities.respond(input).await
+    }
+
+    async fn describe_affected_entities_for_organization(
+        &self,
+        input: DescribeAffectedEntitiesForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedEntitiesForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationError,
+    > {
+        self.affected_entities_for_organization.respond(input).await
+    }
+
+    async fn describe_event_aggregates(
+        &self,
+        input: DescribeEventAggregatesRequest,
+    ) -> RusotoResult<DescribeEventAggregatesResponse, DescribeEventAggregatesError> {
+        self.event_aggregates.respond(input).await
+    }
+
+    async fn describe_event_details(
+        &self,
+        input: DescribeEventDetailsRequest,
+    ) -> RusotoResult<DescribeEventDetailsResponse, DescribeEventDetailsError> {
+        self.event_details.respond(input).await
+    }
+
+    async fn describe_event_details_for_organization(
+        &self,
+        input: DescribeEventDetailsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeEventDetailsForOrganizationResponse,
+        DescribeEventDetailsForOrganizationError,
+    > {
+        self.event_details_for_organization.respond(input).await
+    }
+
+    async fn describe_event_types(
+        &self,
+        input: DescribeEventTypesRequest,
+    ) -> RusotoResult<DescribeEventTypesResponse, DescribeEventTypesError> {
+        self.event_types.respond(input).await
+    }
+
+    async fn describe_health_service_status_for_organization(
+        &self,
+    ) -> RusotoResult<
+        DescribeHealthServiceStatusForOrganizationResponse,
+        DescribeHealthServiceStatusForOrganizationError,
+    > {
+        self.health_service_status_for_organization
+            .respond(())
+            .await
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{opts, GaugeVec, IntGauge, IntGaugeVec};
+
+use super::error::Result;
+use super::{EventSummary, HealthEvent};
+
+/// Metrics built from a single retrieval of the events
+#[derive(Clone)]
+pub(crate) struct EventMetrics {
+    pub events: IntGaugeVec,
+    pub start_times: GaugeVec,
+    pub end_times: GaugeVec,
+    pub last_updated_times: GaugeVec,
+    pub affected_entities: Option<IntGaugeVec>,
+    pub event_counts: Option<IntGaugeVec>,
+    pub event_types: Option<IntGaugeVec>,
+    pub event_descriptions: Option<IntGaugeVec>,
+    pub event_details_failures: Option<IntGauge>,
+    /// Whether the events of the account were retrieved, when querying several accounts
+    pub account_success: Option<IntGauge>,
+    /// Region of the AWS Health endpoint the events were retrieved from
+    pub endpoint: Option<IntGaugeVec>,
+    /// Events behind the metrics, for the JSON API
+    pub event_summaries: Vec<EventSummary>,
+}
+
+impl EventMetrics {
+    /// Create the event metrics, all of them sharing the given labels.
+    ///
+    /// The labels don't identify an event, so the times are also labelled with the event ARN.
+    pub(super) fn new(labels: &[&str], const_labels: HashMap<String, String>) -> Result<Self> {
+        let time_labels = [labels, &["event_arn"]].concat();
+        let events_opts = opts!("aws_health_events", "A list of AWS Health events");
+        let start_times_opts = opts!(
+            "aws_health_event_start_time_seconds",
+            "Start time of AWS Health events"
+        );
+        let end_times_opts = opts!(
+            "aws_health_event_end_time_seconds",
+            "End time of AWS Health events"
+        );
+        let last_updated_times_opts = opts!(
+            "aws_health_event_last_updated_seconds",
+            "Time AWS Health events were last updated"
+        );
+
+        Ok(Self {
+            events: IntGaugeVec::new(events_opts.const_labels(const_labels.clone()), labels)?,
+            start_times: GaugeVec::new(
+                start_times_opts.const_labels(const_labels.clone()),
+                &time_labels,
+            )?,
+            end_times: GaugeVec::new(
+                end_times_opts.const_labels(const_labels.clone()),
+                &time_labels,
+            )?,
+            last_updated_times: GaugeVec::new(
+                last_updated_times_opts.const_labels(const_labels),
+                &time_labels,
+            )?,
+            affected_entities: None,
+            event_counts: None,
+            event_types: None,
+            event_descriptions: None,
+            event_details_failures: None,
+            account_success: None,
+            endpoint: None,
+            event_summaries: vec![],
+        })
+    }
+
+    pub(super) fn set_account_success(
+        &mut self,
+        const_labels: HashMap<String, String>,
+        success: bool,
+    ) -> Result<()> {
+        let opts = opts!(
+            "aws_health_account_success",
+            "Whether retrieval of health events of the account from AWS API was successful"
+        )
+        .const_labels(const_labels);
+        let account_success = IntGauge::with_opts(opts)?;
+        account_success.set(success as i64);
+        self.account_success = Some(account_success);
+        Ok(())
+    }
+
+    /// Set the metrics of a single event series.
+    ///
+    /// Times are only set when provided by the API, ongoing events have no end time.
+    pub(super) fn set_event(
+        &self,
+        labels: &HashMap<&str, &str>,
+        event: &HealthEvent,
+    ) -> Result<()> {
+        self.events.get_metric_with(labels)?.set(1);
+        let mut time_labels = labels.clone();
+        time_labels.insert("event_arn", event.arn.as_deref().unwrap_or(""));
+        if let Some(start_time) = event.start_time {
+            self.start_times
+                .get_metric_with(&time_labels)?
+                .set(start_time);
+        }
+        if let Some(end_time) = event.end_time {
+            self.end_times.get_metric_with(&time_labels)?.set(end_time);
+        }
+        if let Some(last_updated_time) = event.last_updated_time {
+            self.last_updated_times
+                .get_metric_with(&time_labels)?
+                .set(last_updated_time);
+        }
+        Ok(())
+    }
+
+    pub(super) fn set_endpoint(
+        &mut self,
+        const_labels: HashMap<String, String>,
+        health_region: &str,
+    ) -> Result<()> {
+        let opts = opts!(
+            "aws_health_endpoint_info",
+            "AWS Health endpoint the events were retrieved from"
+        )
+        .const_labels(const_labels);
+        let endpoint = IntGaugeVec::new(opts, &["health_region"])?;
+        endpoint
+            .get_metric_with_label_values(&[health_region])?
+            .set(1);
+        self.endpoint = Some(endpoint);
+        Ok(())
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        let mut collectors: Vec<&dyn Collector> = vec![
+            &self.events,
+            &self.start_times,
+            &self.end_times,
+            &self.last_updated_times,
+        ];
+        if let Some(affected_entities) = &self.affected_entities {
+            collectors.push(affected_entities);
+        }
+        if let Some(event_counts) = &self.event_counts {
+            collectors.push(event_counts);
+        }
+        if let Some(event_types) = &self.event_types {
+            collectors.push(event_types);
+        }
+        if let Some(event_descriptions) = &self.event_descriptions {
+            collectors.push(event_descriptions);
+        }
+        if let Some(event_details_failures) = &self.event_details_failures {
+            collectors.push(event_details_failures);
+        }
+        if let Some(account_success) = &self.account_success {
+            collectors.push(account_success);
+        }
+        if let Some(endpoint) = &self.endpoint {
+            collectors.push(endpoint);
+        }
+        collectors
+    }
+}
+
+impl Collector for EventMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|collector| collector.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|collector| collector.collect())
+            .collect()
+    }
+}
@@ -1,86 +1,359 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
-use std::str::FromStr;
-use std::time::Duration;
+use std::path::Path;
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use log::debug;
-use prometheus::{opts, IntGaugeVec};
-use rusoto_core::request::BufferedHttpResponse;
-use rusoto_core::{HttpClient, Region, RusotoError};
+use futures::future::join_all;
+use log::warn;
+use prometheus::IntGauge;
+use rusoto_core::Region;
 use rusoto_health::{
-    AWSHealth, AWSHealthClient, DescribeEventsForOrganizationRequest,
-    DescribeEventsForOrganizationResponse, DescribeEventsRequest, DescribeEventsResponse, Event,
-    EventFilter, OrganizationEvent, OrganizationEventFilter,
+    DateTimeRange, DescribeEventsForOrganizationRequest, DescribeEventsForOrganizationResponse,
+    DescribeEventsRequest, DescribeEventsResponse, Event, EventFilter, OrganizationEvent,
+    OrganizationEventFilter,
 };
-use rusoto_sts::{StsAssumeRoleSessionCredentialsProvider, StsClient};
-use tokio::time::sleep;
-use warp::http::StatusCode;
 
+use backend::{HealthBackend, RusotoBackend, TimeoutBackend};
+pub(crate) use credentials::check_profile;
 use error::{Error, Result};
+pub(crate) use metrics::EventMetrics;
+use pagination::{paginate, PaginatedRequest, PaginatedResponse};
+use replay::{RecordingBackend, ReplayBackend};
+use single_flight::SingleFlight;
+pub(crate) use summary::EventSummary;
 
-use crate::config::Config;
+use crate::config::{AccountRole, Config};
 
+mod accounts;
+mod aggregates;
+mod backend;
+mod credentials;
+mod details;
+mod entities;
 pub(crate) mod error;
-
-// AWS Health API is only available on us-east-1
-static HEALTH_REGION: &str = "us-east-1";
+mod event_types;
+#[cfg(test)]
+pub(crate) mod fake_backend;
+mod metrics;
+mod organization;
+mod pagination;
+mod replay;
+mod single_flight;
+mod summary;
 
 pub(crate) struct Scraper {
-    client: AWSHealthClient,
+    /// One per account to query
+    targets: Arc<Vec<ScraperInner>>,
+    in_flight: SingleFlight<StdResult<Vec<EventMetrics>, Arc<Error>>>,
+}
+
+#[derive(Clone)]
+struct ScraperInner {
+    /// Account the events are labelled with, when querying several accounts
+    account: Option<AccountRole>,
+    /// Same scraper for the passive AWS Health endpoint, used when the active one fails
+    failover: Option<Box<ScraperInner>>,
+    backend: Arc<dyn HealthBackend>,
+    health_region: Region,
+    /// Whether the organizational view is enabled, as of the last check
+    organization_view_enabled: Arc<Mutex<Option<bool>>>,
     regions: Option<Vec<String>>,
     services: Option<Vec<String>>,
+    event_type_categories: Vec<String>,
+    event_status_codes: Option<Vec<String>>,
+    max_start_age: Option<Duration>,
+    max_end_age: Option<Duration>,
+    max_last_updated_age: Option<Duration>,
     locale: Option<String>,
     use_organization: bool,
+    affected_entities: bool,
+    affected_accounts: bool,
+    aggregate_fields: Option<Vec<String>>,
+    export_events: bool,
+    event_details: bool,
+    event_types: bool,
 }
 
 impl Scraper {
-    /// Create a new scraper and handle authentication.
-    ///
-    /// Documentation related to handling assumed roles:
-    /// https://github.com/rusoto/rusoto/blob/master/AWS-CREDENTIALS.md
+    /// Create a new scraper, with one client per account to query.
     pub fn new(config: &Config) -> Result<Self> {
-        let health_region = Region::from_str(HEALTH_REGION)?;
-
-        let client = match &config.role {
-            None => AWSHealthClient::new(health_region),
-            Some(role) => {
-                let sts_region = match &config.role_region {
-                    Some(region) => Region::from_str(region)?,
-                    None => Region::default(),
-                };
-                let sts = StsClient::new(sts_region);
-
-                let sts_provider = StsAssumeRoleSessionCredentialsProvider::new(
-                    sts,
-                    role.to_owned(),
-                    "aws-health-exporter".to_owned(),
-                    None,
-                    None,
-                    None,
-                    None,
+        let targets = match &config.account_roles {
+            None => vec![ScraperInner::new(config, config.role.as_deref(), None)?],
+            Some(account_roles) => account_roles
+                .iter()
+                .map(|account_role| {
+                    ScraperInner::new(config, Some(&account_role.role), Some(account_role))
+                })
+                .collect::<Result<_>>()?,
+        };
+
+        Ok(Self {
+            targets: Arc::new(targets),
+            in_flight: SingleFlight::default(),
+        })
+    }
+
+    /// Create a scraper for a single account, ignoring the roles from the configuration.
+    ///
+    /// Its pages are recorded to, and replayed from, a directory per role.
+    pub fn for_role(config: &Config, role: &str) -> Result<Self> {
+        let mut config = config.clone();
+        config.record_dir = config.record_dir.map(|dir| role_dir(&dir, role));
+        config.replay_dir = config.replay_dir.map(|dir| role_dir(&dir, role));
+        Ok(Self {
+            targets: Arc::new(vec![ScraperInner::new(&config, Some(role), None)?]),
+            in_flight: SingleFlight::default(),
+        })
+    }
+
+    /// Create a scraper for a single account, sending its calls to the given fake.
+    #[cfg(test)]
+    pub fn with_fake_backend(config: &Config, backend: Arc<fake_backend::FakeBackend>) -> Self {
+        let target =
+            ScraperInner::with_backend(config, None, backend, config.partition.health_region());
+        Self {
+            targets: Arc::new(vec![target]),
+            in_flight: SingleFlight::default(),
+        }
+    }
+
+    /// Retrieve the events from the AWS API.
+    ///
+    /// Concurrent calls share a single walk through the API pages, as well as its result.
+    pub async fn describe_events(&self) -> Result<Vec<EventMetrics>> {
+        let targets = self.targets.clone();
+        self.in_flight
+            .run(move || async move { describe_all_targets(&targets).await.map_err(Arc::new) })
+            .await
+            .map_err(Error::Shared)
+    }
+
+    /// Status of the organizational view of each account, once checked by a retrieval of organization events.
+    pub fn organization_view_metrics(&self) -> Result<Vec<IntGauge>> {
+        let mut metrics = vec![];
+        for target in self.targets.iter() {
+            metrics.extend(target.organization_view_metric()?);
+        }
+        Ok(metrics)
+    }
+
+    /// Check that the services the events are filtered on actually exist.
+    pub async fn validate_services(&self) -> Result<()> {
+        // All the accounts share the same catalog
+        let target = &self.targets[0];
+        let services = match &target.services {
+            Some(services) => services,
+            None => return Ok(()),
+        };
+        let known_services: HashSet<String> = target
+            .describe_event_types()
+            .await?
+            .into_iter()
+            .filter_map(|event_type| event_type.service)
+            .collect();
+        let unknown_services: Vec<String> = services
+            .iter()
+            .filter(|service| !known_services.contains(*service))
+            .cloned()
+            .collect();
+        if unknown_services.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::UnknownServices(unknown_services))
+        }
+    }
+}
+
+/// Directory of the recordings of a probed role, named after its ARN without the characters which aren't safe in paths
+fn role_dir(dir: &str, role: &str) -> String {
+    let name: String = role
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '+' | '=' | ',' | '.' | '@' | '-' => c,
+            _ => '_',
+        })
+        .collect();
+    Path::new(dir)
+        .join("probe")
+        .join(name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Send the requests for the region to the given endpoint instead, if any.
+///
+/// Requests are still signed for the region.
+fn custom_endpoint(region: Region, endpoint: Option<&str>) -> Region {
+    match endpoint {
+        Some(endpoint) => Region::Custom {
+            name: region.name().to_string(),
+            endpoint: endpoint.to_string(),
+        },
+        None => region,
+    }
+}
+
+/// Retrieve the events of all the accounts concurrently.
+///
+/// When querying several accounts, a failure in one of them is only reflected by its success metric,
+/// unless all of them fail.
+async fn describe_all_targets(targets: &[ScraperInner]) -> Result<Vec<EventMetrics>> {
+    if let [target] = targets {
+        if target.account.is_none() {
+            return Ok(vec![target.describe_events().await?]);
+        }
+    }
+
+    let results = join_all(targets.iter().map(ScraperInner::describe_events)).await;
+    let mut all_event_metrics = vec![];
+    let mut failures = 0;
+    let mut last_error = None;
+    for (target, result) in targets.iter().zip(results) {
+        let (mut event_metrics, success) = match result {
+            Ok(event_metrics) => (event_metrics, true),
+            Err(err) => {
+                warn!(
+                    "Failed to retrieve events for account {}: {}",
+                    target.account_description(),
+                    err
                 );
-                let auto_refreshing_provider =
-                    rusoto_credential::AutoRefreshingProvider::new(sts_provider)?;
-                AWSHealthClient::new_with(
-                    HttpClient::new()?,
-                    auto_refreshing_provider,
-                    health_region,
+                failures += 1;
+                last_error = Some(err);
+                (
+                    EventMetrics::new(&target.event_labels(), target.const_labels())?,
+                    false,
                 )
             }
         };
+        event_metrics.set_account_success(target.const_labels(), success)?;
+        all_event_metrics.push(event_metrics);
+    }
 
-        Ok(Self {
-            client,
+    match last_error {
+        Some(err) if failures == targets.len() => Err(err),
+        _ => Ok(all_event_metrics),
+    }
+}
+
+impl ScraperInner {
+    fn new(config: &Config, role: Option<&str>, account: Option<&AccountRole>) -> Result<Self> {
+        // AWS Health API is only available in one region per partition
+        let health_region = custom_endpoint(
+            config.partition.health_region(),
+            config.health_endpoint.as_deref(),
+        );
+        // Recordings of several accounts go to a directory per account
+        let account_dir = |dir: &str| match account {
+            Some(account) => Path::new(dir).join(&account.account_id),
+            None => Path::new(dir).to_path_buf(),
+        };
+        if let Some(replay_dir) = &config.replay_dir {
+            let backend = ReplayBackend::new(account_dir(replay_dir));
+            return Ok(Self::with_backend(
+                config,
+                account,
+                Arc::new(backend),
+                health_region,
+            ));
+        }
+
+        let core_client = credentials::new_client(config, role)?;
+        let record = |backend: RusotoBackend| -> Arc<dyn HealthBackend> {
+            let backend = Arc::new(TimeoutBackend::new(
+                Arc::new(backend),
+                config.endpoint_timeout,
+            ));
+            match &config.record_dir {
+                Some(record_dir) => {
+                    Arc::new(RecordingBackend::new(backend, account_dir(record_dir)))
+                }
+                None => backend,
+            }
+        };
+        let backend = RusotoBackend::new(core_client.clone(), health_region.clone());
+        let mut scraper = Self::with_backend(config, account, record(backend), health_region);
+        if config.health_endpoint.is_none() {
+            if let Some(failover_region) = config.partition.health_failover_region() {
+                let backend = RusotoBackend::new(core_client, failover_region.clone());
+                scraper.failover = Some(Box::new(Self {
+                    backend: record(backend),
+                    health_region: failover_region,
+                    ..scraper.clone()
+                }));
+            }
+        }
+        Ok(scraper)
+    }
+
+    /// Create a scraper sending its calls to the given backend.
+    fn with_backend(
+        config: &Config,
+        account: Option<&AccountRole>,
+        backend: Arc<dyn HealthBackend>,
+        health_region: Region,
+    ) -> Self {
+        Self {
+            account: account.cloned(),
+            failover: None,
+            backend,
+            health_region,
+            organization_view_enabled: Arc::default(),
             regions: config.regions.to_owned(),
             locale: Some("en".into()),
             services: config.services.to_owned(),
+            event_type_categories: config.event_type_categories.to_owned(),
+            event_status_codes: config.event_status_codes.to_owned(),
+            max_start_age: config.max_start_age,
+            max_end_age: config.max_end_age,
+            max_last_updated_age: config.max_last_updated_age,
             use_organization: config.use_organization,
-        })
+            affected_entities: config.affected_entities,
+            affected_accounts: config.affected_accounts,
+            aggregate_fields: config.aggregate_fields.to_owned(),
+            export_events: config.export_events,
+            event_details: config.event_details,
+            event_types: config.event_types,
+        }
     }
 
-    pub async fn describe_events(&self) -> Result<IntGaugeVec> {
-        let opts = opts!("aws_health_events", "A list of AWS Health events");
-        let labels = if self.use_organization {
+    /// Labels set on all the series of the account, when querying several accounts
+    fn const_labels(&self) -> HashMap<String, String> {
+        let mut const_labels = HashMap::new();
+        if let Some(account) = &self.account {
+            const_labels.insert("account_id".to_string(), account.account_id.to_owned());
+            const_labels.insert(
+                "account_name".to_string(),
+                account.name.to_owned().unwrap_or_default(),
+            );
+        }
+        const_labels
+    }
+
+    fn account_description(&self) -> String {
+        match &self.account {
+            Some(AccountRole {
+                account_id,
+                name: Some(name),
+                ..
+            }) => format!("{} ({})", account_id, name),
+            Some(account) => account.account_id.to_owned(),
+            None => "".to_string(),
+        }
+    }
+
+    fn event_labels(&self) -> Vec<&'static str> {
+        if self.use_organization && self.affected_accounts {
+            vec![
+                "account_id",
+                "event_type_category",
+                "event_type_code",
+                "region",
+                "service",
+                "status",
+            ]
+        } else if self.use_organization {
             vec![
                 "event_type_category",
                 "event_type_code",
@@ -97,86 +370,218 @@ impl Scraper {
                 "service",
                 "status",
             ]
-        };
-        let event_metrics = IntGaugeVec::new(opts, &labels)?;
+        }
+    }
 
-        let next_token: Option<String> = None;
-        let generic_filter = GenericFilter {
-            regions: self.regions.to_owned(),
-            services: self.services.to_owned(),
-            event_type_categories: Some(vec!["issue".to_string(), "scheduledChange".to_string()]),
+    /// Retrieve the events from the active AWS Health endpoint,
+    /// walking through them again from the passive one if the former is impaired.
+    ///
+    /// Organization events are skipped when the organizational view of the account is disabled.
+    async fn describe_events(&self) -> Result<EventMetrics> {
+        if self.use_organization {
+            self.require_organization_view().await?;
+        }
+        let (result, scraper) = match (self.describe_endpoint_events().await, &self.failover) {
+            (Err(err), Some(failover)) if err.is_endpoint_failure() => {
+                warn!(
+                    "AWS Health endpoint in {} failed, trying {}: {}",
+                    self.health_region.name(),
+                    failover.health_region.name(),
+                    err
+                );
+                (failover.describe_endpoint_events().await, failover.as_ref())
+            }
+            (result, _) => (result, self),
         };
+        let mut event_metrics = result?;
+        event_metrics.set_endpoint(scraper.const_labels(), scraper.health_region.name())?;
+        Ok(event_metrics)
+    }
 
-        let mut request = GenericRequest {
-            filter: Some(generic_filter),
-            locale: self.locale.to_owned(),
-            max_results: None,
-            next_token: next_token.to_owned(),
-        };
+    async fn describe_endpoint_events(&self) -> Result<EventMetrics> {
+        let with_accounts = self.use_organization && self.affected_accounts;
+        let mut event_metrics = EventMetrics::new(&self.event_labels(), self.const_labels())?;
 
-        // Implement a poor man's backoff
-        // As documented on "Handling errors / Error retries and exponential backoff"
-        // https://docs.aws.amazon.com/elastictranscoder/latest/developerguide/error-handling.html#api-retries
-        let mut retry: u32 = 0;
-        let wait_base: u32 = 2;
-        loop {
-            if retry > 10 {
-                return Err(Error::TooManyRetries);
+        if self.export_events {
+            let mut events = self.describe_all_events().await?;
+            if with_accounts {
+                self.describe_affected_accounts(&mut events).await?;
             }
-            if retry > 0 {
-                let delay = Duration::from_millis(50) * wait_base.pow(retry);
-                debug!("Got TooManyRequests. Sleeping for {:#?}...", delay);
-                sleep(delay).await;
+            set_event_metrics(&events, &event_metrics, with_accounts)?;
+
+            if self.event_details {
+                let failures = self.describe_event_details(&mut events).await?;
+                let metric_family = details::new_event_description_metrics(self.const_labels())?;
+                details::set_description_metrics(&events, &metric_family)?;
+                let failure_metric =
+                    details::new_event_details_failure_metric(self.const_labels())?;
+                failure_metric.set(failures);
+                event_metrics.event_descriptions = Some(metric_family);
+                event_metrics.event_details_failures = Some(failure_metric);
             }
-            let response: Box<dyn GenericResponse> = if self.use_organization {
-                let request = request.clone().into();
-                match self.client.describe_events_for_organization(request).await {
-                    Ok(response) => Box::new(response),
-                    Err(RusotoError::Unknown(BufferedHttpResponse {
-                        status: StatusCode::TOO_MANY_REQUESTS,
-                        ..
-                    })) => {
-                        retry += 1;
-                        continue;
-                    }
-                    Err(err) => return Err(err.into()),
-                }
-            } else {
-                let request = request.clone().into();
-                match self.client.describe_events(request).await {
-                    Ok(response) => Box::new(response),
-                    Err(RusotoError::Unknown(BufferedHttpResponse {
-                        status: StatusCode::TOO_MANY_REQUESTS,
-                        ..
-                    })) => {
-                        retry += 1;
-                        continue;
-                    }
-                    Err(err) => return Err(err.into()),
-                }
-            };
-            response.get_events(&event_metrics)?;
-            match response.get_next_token() {
-                Some(token) => request.next_token = Some(token),
-                None => break,
+
+            event_metrics.event_summaries = events
+                .iter()
+                .map(|event| EventSummary::new(event, self.account.as_ref()))
+                .collect();
+
+            if self.affected_entities {
+                let metric_family = entities::new_affected_entity_metrics(self.const_labels())?;
+                let open_events: Vec<&HealthEvent> =
+                    events.iter().filter(|event| event.is_open()).collect();
+                self.describe_affected_entities(&open_events, &metric_family)
+                    .await?;
+                event_metrics.affected_entities = Some(metric_family);
+            }
+        }
+
+        if let Some(aggregate_fields) = &self.aggregate_fields {
+            let metric_family = aggregates::new_event_count_metrics(self.const_labels())?;
+            for aggregate_field in aggregate_fields {
+                self.describe_event_aggregates(aggregate_field, &metric_family)
+                    .await?;
+            }
+            event_metrics.event_counts = Some(metric_family);
+        }
+
+        if self.event_types {
+            let metric_family = event_types::new_event_type_metrics(self.const_labels())?;
+            for event_type in self.describe_event_types().await? {
+                event_type.set_metric(&metric_family)?;
             }
-            retry = 0;
+            event_metrics.event_types = Some(metric_family);
         }
 
         Ok(event_metrics)
     }
+
+    /// Walk through all the pages of events.
+    async fn describe_all_events(&self) -> Result<Vec<HealthEvent>> {
+        let request = GenericRequest {
+            filter: Some(self.generic_filter()),
+            locale: self.locale.to_owned(),
+            max_results: None,
+            next_token: None,
+        };
+
+        let mut events = vec![];
+        let mut handle_page = |response: &dyn GenericResponse| -> Result<()> {
+            events.extend(response.get_events());
+            Ok(())
+        };
+        if self.use_organization {
+            paginate(
+                DescribeEventsForOrganizationRequest::from(request),
+                |request| self.backend.describe_events_for_organization(request),
+                |response| handle_page(&response),
+            )
+            .await?;
+        } else {
+            paginate(
+                DescribeEventsRequest::from(request),
+                |request| self.backend.describe_events(request),
+                |response| handle_page(&response),
+            )
+            .await?;
+        }
+        Ok(events)
+    }
+
+    fn generic_filter(&self) -> GenericFilter {
+        GenericFilter {
+            regions: self.regions.to_owned(),
+            services: self.services.to_owned(),
+            event_type_categories: Some(self.event_type_categories.to_owned()),
+            event_status_codes: self.event_status_codes.to_owned(),
+            start_time: self.max_start_age.map(date_time_range_since),
+            end_time: self.max_end_age.map(date_time_range_since),
+            last_updated_time: self.max_last_updated_age.map(date_time_range_since),
+        }
+    }
 }
 
-fn handle_events<T: GenericEvent>(events: &[T], metric_family: &IntGaugeVec) -> Result<()> {
+/// Event retrieved from the AWS API, independently of the kind of call
+struct HealthEvent {
+    arn: Option<String>,
+    fields: HashMap<String, String>,
+    start_time: Option<f64>,
+    end_time: Option<f64>,
+    last_updated_time: Option<f64>,
+    scope: Option<String>,
+    /// Accounts affected by an organization event, only retrieved when requested
+    account_ids: Vec<String>,
+    /// Latest description, only retrieved when requested
+    description: Option<String>,
+}
+
+impl HealthEvent {
+    /// Events which aren't closed may still have affected entities
+    fn is_open(&self) -> bool {
+        self.fields["status"] != "closed"
+    }
+
+    /// Public events aren't specific to any account
+    fn is_public(&self) -> bool {
+        self.scope.as_deref() == Some("PUBLIC")
+    }
+}
+
+fn handle_events<T: GenericEvent>(events: &[T]) -> Vec<HealthEvent> {
+    events
+        .iter()
+        .map(|event| HealthEvent {
+            arn: event.get_arn().map(str::to_string),
+            fields: event
+                .get_fields()
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            start_time: event.get_start_time(),
+            end_time: event.get_end_time(),
+            last_updated_time: event.get_last_updated_time(),
+            scope: event.get_event_scope_code().map(str::to_string),
+            account_ids: vec![],
+            description: None,
+        })
+        .collect()
+}
+
+/// Set the metrics for the given events, with one series per affected account if `with_accounts`.
+fn set_event_metrics(
+    events: &[HealthEvent],
+    event_metrics: &EventMetrics,
+    with_accounts: bool,
+) -> Result<()> {
     for event in events {
-        let metric = metric_family.get_metric_with(&event.get_fields())?;
-        metric.set(1);
+        let mut labels: HashMap<&str, &str> = event
+            .fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        if !with_accounts {
+            event_metrics.set_event(&labels, event)?;
+            continue;
+        }
+        // Public events don't affect any account in particular
+        let account_ids = match event.account_ids.as_slice() {
+            [] => vec![""],
+            account_ids => account_ids.iter().map(String::as_str).collect(),
+        };
+        for account_id in account_ids {
+            labels.insert("account_id", account_id);
+            event_metrics.set_event(&labels, event)?;
+        }
     }
     Ok(())
 }
 
 trait GenericEvent {
     fn get_fields(&self) -> HashMap<&str, &str>;
+    fn get_arn(&self) -> Option<&str>;
+    fn get_start_time(&self) -> Option<f64>;
+    fn get_end_time(&self) -> Option<f64>;
+    fn get_last_updated_time(&self) -> Option<f64>;
+    fn get_event_scope_code(&self) -> Option<&str>;
 }
 
 impl GenericEvent for Event {
@@ -199,6 +604,26 @@ impl GenericEvent for Event {
 
         label_map
     }
+
+    fn get_arn(&self) -> Option<&str> {
+        self.arn.as_deref()
+    }
+
+    fn get_start_time(&self) -> Option<f64> {
+        self.start_time
+    }
+
+    fn get_end_time(&self) -> Option<f64> {
+        self.end_time
+    }
+
+    fn get_last_updated_time(&self) -> Option<f64> {
+        self.last_updated_time
+    }
+
+    fn get_event_scope_code(&self) -> Option<&str> {
+        self.event_scope_code.as_deref()
+    }
 }
 
 impl GenericEvent for OrganizationEvent {
@@ -219,6 +644,26 @@ impl GenericEvent for OrganizationEvent {
 
         label_map
     }
+
+    fn get_arn(&self) -> Option<&str> {
+        self.arn.as_deref()
+    }
+
+    fn get_start_time(&self) -> Option<f64> {
+        self.start_time
+    }
+
+    fn get_end_time(&self) -> Option<f64> {
+        self.end_time
+    }
+
+    fn get_last_updated_time(&self) -> Option<f64> {
+        self.last_updated_time
+    }
+
+    fn get_event_scope_code(&self) -> Option<&str> {
+        self.event_scope_code.as_deref()
+    }
 }
 
 #[derive(Clone)]
@@ -226,6 +671,21 @@ struct GenericFilter {
     regions: Option<Vec<String>>,
     services: Option<Vec<String>>,
     event_type_categories: Option<Vec<String>>,
+    event_status_codes: Option<Vec<String>>,
+    start_time: Option<DateTimeRange>,
+    end_time: Option<DateTimeRange>,
+    last_updated_time: Option<DateTimeRange>,
+}
+
+/// Time range starting `age` ago, open-ended so that it includes the future
+fn date_time_range_since(age: Duration) -> DateTimeRange {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    DateTimeRange {
+        from: Some(now.saturating_sub(age).as_secs_f64()),
+        to: None,
+    }
 }
 
 impl From<GenericFilter> for EventFilter {
@@ -234,6 +694,10 @@ impl From<GenericFilter> for EventFilter {
             regions: generic_filter.regions,
             services: generic_filter.services,
             event_type_categories: generic_filter.event_type_categories,
+            event_status_codes: generic_filter.event_status_codes,
+            start_times: generic_filter.start_time.map(|range| vec![range]),
+            end_times: generic_filter.end_time.map(|range| vec![range]),
+            last_updated_times: generic_filter.last_updated_time.map(|range| vec![range]),
             ..Default::default()
         }
     }
@@ -245,6 +709,10 @@ impl From<GenericFilter> for OrganizationEventFilter {
             regions: generic_filter.regions,
             services: generic_filter.services,
             event_type_categories: generic_filter.event_type_categories,
+            event_status_codes: generic_filter.event_status_codes,
+            start_time: generic_filter.start_time,
+            end_time: generic_filter.end_time,
+            last_updated_time: generic_filter.last_updated_time,
             ..Default::default()
         }
     }
@@ -280,33 +748,1203 @@ impl From<GenericRequest> for DescribeEventsForOrganizationRequest {
     }
 }
 
+impl PaginatedRequest for DescribeEventsRequest {
+    fn set_next_token(&mut self, next_token: Option<String>) {
+        self.next_token = next_token;
+    }
+}
+
+impl PaginatedRequest for DescribeEventsForOrganizationRequest {
+    fn set_next_token(&mut self, next_token: Option<String>) {
+        self.next_token = next_token;
+    }
+}
+
 trait GenericResponse {
-    fn get_next_token(&self) -> Option<String>;
-    fn get_events(&self, metric_family: &IntGaugeVec) -> Result<()>;
+    fn get_events(&self) -> Vec<HealthEvent>;
 }
 
-impl GenericResponse for DescribeEventsResponse {
+impl PaginatedResponse for DescribeEventsResponse {
     fn get_next_token(&self) -> Option<String> {
         self.next_token.clone()
     }
+}
 
-    fn get_events(&self, metric_family: &IntGaugeVec) -> Result<()> {
-        if let Some(events) = &self.events {
-            handle_events(events, metric_family)?;
+impl GenericResponse for DescribeEventsResponse {
+    fn get_events(&self) -> Vec<HealthEvent> {
+        match &self.events {
+            Some(events) => handle_events(events),
+            None => vec![],
         }
-        Ok(())
     }
 }
 
-impl GenericResponse for DescribeEventsForOrganizationResponse {
+impl PaginatedResponse for DescribeEventsForOrganizationResponse {
     fn get_next_token(&self) -> Option<String> {
         self.next_token.clone()
     }
+}
 
-    fn get_events(&self, metric_family: &IntGaugeVec) -> Result<()> {
-        if let Some(events) = &self.events {
-            handle_events(events, metric_family)?;
+impl GenericResponse for DescribeEventsForOrganizationResponse {
+    fn get_events(&self) -> Vec<HealthEvent> {
+        match &self.events {
+            Some(events) => handle_events(events),
+            None => vec![],
         }
-        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::details::{
+        DescribeEventDetailsForOrganizationResponse, DescribeEventDetailsResponse,
+        EventDescription, EventDetails,
+    };
+    use super::fake_backend::FakeBackend;
+    use super::*;
+    use prometheus::core::Collector;
+    use rusoto_core::request::HttpDispatchError;
+    use rusoto_core::RusotoError;
+    use rusoto_health::{
+        AffectedEntity, DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationResponse, DescribeAffectedEntitiesResponse,
+        DescribeEventAggregatesResponse, DescribeEventsError,
+        DescribeEventsForOrganizationResponse, DescribeHealthServiceStatusForOrganizationResponse,
+        EventAggregate, EventDetailsErrorItem, OrganizationEventDetailsErrorItem,
+    };
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    fn scraper(args: &[&str], backend: Arc<dyn HealthBackend>) -> ScraperInner {
+        let args = ["aws_health_exporter"].iter().chain(args);
+        let config = Config::parse(args).unwrap();
+        ScraperInner::with_backend(&config, None, backend, Region::UsEast1)
+    }
+
+    fn event(code: &str, status: &str) -> Event {
+        Event {
+            arn: Some(format!("arn:aws:health:us-east-1::event/EC2/{}/1", code)),
+            availability_zone: Some("us-east-1a".into()),
+            event_type_category: Some("issue".into()),
+            event_type_code: Some(code.into()),
+            region: Some("us-east-1".into()),
+            service: Some("EC2".into()),
+            status_code: Some(status.into()),
+            start_time: Some(1600000000.0),
+            ..Default::default()
+        }
+    }
+
+    fn page(events: Vec<Event>, next_token: Option<&str>) -> DescribeEventsResponse {
+        DescribeEventsResponse {
+            events: Some(events),
+            next_token: next_token.map(str::to_string),
+        }
+    }
+
+    /// Labels of each series of the collector, sorted for comparison
+    fn series(collector: &dyn Collector) -> Vec<BTreeMap<String, String>> {
+        let mut series: Vec<BTreeMap<String, String>> = collector
+            .collect()
+            .iter()
+            .flat_map(|metric_family| metric_family.get_metric())
+            .map(|metric| {
+                metric
+                    .get_label()
+                    .iter()
+                    .map(|label| (label.get_name().into(), label.get_value().into()))
+                    .collect()
+            })
+            .collect();
+        series.sort();
+        series
+    }
+
+    fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn event_fields_are_mapped_to_labels() {
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_page(page(vec![event("AWS_EC2_OPERATIONAL_ISSUE", "open")], None));
+
+        let event_metrics = scraper(&[], backend).describe_events().await.unwrap();
+
+        let expected = labels(&[
+            ("availability_zone", "us-east-1a"),
+            ("event_type_category", "issue"),
+            ("event_type_code", "AWS_EC2_OPERATIONAL_ISSUE"),
+            ("region", "us-east-1"),
+            ("service", "EC2"),
+            ("status", "open"),
+        ]);
+        assert_eq!(series(&event_metrics.events), vec![expected.clone()]);
+        let mut expected_times = expected;
+        expected_times.insert(
+            "event_arn".to_string(),
+            "arn:aws:health:us-east-1::event/EC2/AWS_EC2_OPERATIONAL_ISSUE/1".to_string(),
+        );
+        assert_eq!(series(&event_metrics.start_times), vec![expected_times]);
+        assert!(series(&event_metrics.end_times).is_empty());
+        assert_eq!(
+            series(event_metrics.endpoint.as_ref().unwrap()),
+            vec![labels(&[("health_region", "us-east-1")])]
+        );
+    }
+
+    #[tokio::test]
+    async fn times_are_kept_for_each_event() {
+        let backend = Arc::new(FakeBackend::default());
+        let mut later = event("AWS_EC2_OPERATIONAL_ISSUE", "open");
+        later.arn = Some("arn:aws:health:us-east-1::event/EC2/AWS_EC2_OPERATIONAL_ISSUE/2".into());
+        later.start_time = Some(1600003600.0);
+        backend.events.push_page(page(
+            vec![event("AWS_EC2_OPERATIONAL_ISSUE", "open"), later],
+            None,
+        ));
+
+        let event_metrics = scraper(&[], backend).describe_events().await.unwrap();
+
+        assert_eq!(series(&event_metrics.events).len(), 1);
+        let mut start_times: Vec<f64> = event_metrics
+            .start_times
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .map(|metric| metric.get_gauge().get_value())
+            .collect();
+        start_times.sort_by(f64::total_cmp);
+        assert_eq!(start_times, vec![1600000000.0, 1600003600.0]);
+    }
+
+    #[tokio::test]
+    async fn all_pages_are_retrieved() {
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_page(page(vec![event("FIRST", "open")], Some("token-1")))
+            .push_page(page(vec![event("SECOND", "closed")], Some("token-2")))
+            .push_page(page(vec![event("THIRD", "upcoming")], None));
+
+        let event_metrics = scraper(&[], backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        assert_eq!(series(&event_metrics.events).len(), 3);
+        let next_tokens: Vec<_> = backend
+            .events
+            .requests()
+            .into_iter()
+            .map(|request| request.next_token)
+            .collect();
+        assert_eq!(
+            next_tokens,
+            vec![None, Some("token-1".into()), Some("token-2".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls_share_a_single_walk() {
+        tokio::time::pause();
+        let backend = Arc::new(FakeBackend::default());
+        // The walk waits before retrying the first page, while the other calls arrive
+        backend
+            .events
+            .push_throttling()
+            .push_page(page(vec![event("FIRST", "open")], Some("token-1")))
+            .push_page(page(vec![event("SECOND", "open")], None));
+        let scraper = Scraper {
+            targets: Arc::new(vec![scraper(&[], backend.clone())]),
+            in_flight: SingleFlight::default(),
+        };
+
+        let results = join_all((0..10).map(|_| scraper.describe_events())).await;
+
+        for result in results {
+            assert_eq!(series(&result.unwrap()[0].events).len(), 2);
+        }
+        let next_tokens: Vec<_> = backend
+            .events
+            .requests()
+            .into_iter()
+            .map(|request| request.next_token)
+            .collect();
+        assert_eq!(next_tokens, vec![None, None, Some("token-1".into())]);
+    }
+
+    #[tokio::test]
+    async fn filters_default_to_issues_and_scheduled_changes() {
+        let backend = Arc::new(FakeBackend::default());
+
+        scraper(&[], backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let filter = backend.events.requests()[0].filter.clone().unwrap();
+        assert_eq!(
+            filter.event_type_categories,
+            Some(vec!["issue".to_string(), "scheduledChange".to_string()])
+        );
+        assert_eq!(filter.event_status_codes, None);
+        assert_eq!(filter.regions, None);
+        assert_eq!(filter.services, None);
+    }
+
+    #[tokio::test]
+    async fn filters_are_sent_to_the_account_api() {
+        let backend = Arc::new(FakeBackend::default());
+        let args = [
+            "--category",
+            "accountNotification",
+            "--status",
+            "open",
+            "--status",
+            "upcoming",
+            "--region",
+            "eu-west-1",
+            "--service",
+            "EC2",
+        ];
+
+        scraper(&args, backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let filter = backend.events.requests()[0].filter.clone().unwrap();
+        assert_eq!(
+            filter.event_type_categories,
+            Some(vec!["accountNotification".to_string()])
+        );
+        assert_eq!(
+            filter.event_status_codes,
+            Some(vec!["open".to_string(), "upcoming".to_string()])
+        );
+        // Global events are kept along with those of the region
+        assert_eq!(
+            filter.regions,
+            Some(vec!["eu-west-1".to_string(), "global".to_string()])
+        );
+        assert_eq!(filter.services, Some(vec!["EC2".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn filters_are_sent_to_the_organization_api() {
+        let backend = organization_backend(organization::ENABLED_STATUS);
+        let args = ["--organization", "--status", "closed", "--service", "RDS"];
+
+        scraper(&args, backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let filter = backend.events_for_organization.requests()[0]
+            .filter
+            .clone()
+            .unwrap();
+        assert_eq!(
+            filter.event_type_categories,
+            Some(vec!["issue".to_string(), "scheduledChange".to_string()])
+        );
+        assert_eq!(filter.event_status_codes, Some(vec!["closed".to_string()]));
+        assert_eq!(filter.services, Some(vec!["RDS".to_string()]));
+        assert!(backend.events.requests().is_empty());
+    }
+
+    /// Seconds since the epoch, `age` ago
+    fn seconds_ago(age: u64) -> f64 {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        (now - Duration::from_secs(age)).as_secs_f64()
+    }
+
+    /// Whether the range starts between the given times, and is open-ended
+    fn starts_between(range: &DateTimeRange, earliest: f64, latest: f64) -> bool {
+        let from = range.from.unwrap();
+        earliest <= from && from <= latest && range.to.is_none()
+    }
+
+    #[tokio::test]
+    async fn max_ages_are_sent_to_the_account_api() {
+        let backend = Arc::new(FakeBackend::default());
+        let args = ["--max-start-age", "86400", "--max-last-updated-age", "3600"];
+
+        let (start_earliest, updated_earliest) = (seconds_ago(86400), seconds_ago(3600));
+        scraper(&args, backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+        let (start_latest, updated_latest) = (seconds_ago(86400), seconds_ago(3600));
+
+        let filter = backend.events.requests()[0].filter.clone().unwrap();
+        let start_times = filter.start_times.unwrap();
+        assert_eq!(start_times.len(), 1);
+        assert!(starts_between(
+            &start_times[0],
+            start_earliest,
+            start_latest
+        ));
+        let last_updated_times = filter.last_updated_times.unwrap();
+        assert_eq!(last_updated_times.len(), 1);
+        assert!(starts_between(
+            &last_updated_times[0],
+            updated_earliest,
+            updated_latest
+        ));
+        assert_eq!(filter.end_times, None);
+    }
+
+    #[tokio::test]
+    async fn max_ages_are_sent_to_the_organization_api() {
+        let backend = organization_backend(organization::ENABLED_STATUS);
+        let args = ["--organization", "--max-end-age", "604800"];
+
+        let earliest = seconds_ago(604800);
+        scraper(&args, backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+        let latest = seconds_ago(604800);
+
+        let filter = backend.events_for_organization.requests()[0]
+            .filter
+            .clone()
+            .unwrap();
+        assert!(starts_between(&filter.end_time.unwrap(), earliest, latest));
+        assert_eq!(filter.start_time, None);
+        assert_eq!(filter.last_updated_time, None);
+    }
+
+    #[tokio::test]
+    async fn no_time_range_is_sent_by_default() {
+        let backend = Arc::new(FakeBackend::default());
+
+        scraper(&[], backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let filter = backend.events.requests()[0].filter.clone().unwrap();
+        assert_eq!(filter.start_times, None);
+        assert_eq!(filter.end_times, None);
+        assert_eq!(filter.last_updated_times, None);
+    }
+
+    #[tokio::test]
+    async fn throttled_calls_are_retried() {
+        tokio::time::pause();
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_page(page(vec![event("FIRST", "open")], Some("token-1")))
+            .push_throttling()
+            .push_throttling()
+            .push_page(page(vec![event("SECOND", "open")], None));
+
+        let event_metrics = scraper(&[], backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        assert_eq!(series(&event_metrics.events).len(), 2);
+        let requests = backend.events.requests();
+        assert_eq!(requests.len(), 4);
+        // Retries ask for the same page again
+        assert!(requests[1..]
+            .iter()
+            .all(|request| request.next_token.as_deref() == Some("token-1")));
+    }
+
+    #[tokio::test]
+    async fn throttling_gives_up_after_ten_retries() {
+        tokio::time::pause();
+        let backend = Arc::new(FakeBackend::default());
+        for _ in 0..11 {
+            backend.events.push_throttling();
+        }
+        backend
+            .events
+            .push_page(page(vec![event("LATE", "open")], None));
+
+        let result = scraper(&[], backend.clone()).describe_events().await;
+
+        assert!(matches!(result, Err(Error::TooManyRetries)));
+        assert_eq!(backend.events.requests().len(), 11);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_not_retried() {
+        let backend = Arc::new(FakeBackend::default());
+        backend.events.push_error(RusotoError::Service(
+            DescribeEventsError::UnsupportedLocale("en".into()),
+        ));
+
+        let result = scraper(&[], backend.clone()).describe_events().await;
+
+        assert!(matches!(result, Err(Error::DescribeEvents(_))));
+        assert_eq!(backend.events.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn organization_events_have_one_series_per_affected_account() {
+        let backend = organization_backend(organization::ENABLED_STATUS);
+        backend
+            .events_for_organization
+            .push_page(DescribeEventsForOrganizationResponse {
+                events: Some(vec![OrganizationEvent {
+                    arn: Some("arn:aws:health:global::event/IAM/ISSUE/1".into()),
+                    event_type_category: Some("accountNotification".into()),
+                    event_type_code: Some("AWS_IAM_ISSUE".into()),
+                    region: Some("global".into()),
+                    service: Some("IAM".into()),
+                    status_code: Some("open".into()),
+                    ..Default::default()
+                }]),
+                next_token: None,
+            });
+        backend
+            .affected_accounts_for_organization
+            .push_page(DescribeAffectedAccountsForOrganizationResponse {
+                affected_accounts: Some(vec!["111111111111".into()]),
+                next_token: Some("token-1".into()),
+                ..Default::default()
+            })
+            .push_throttling()
+            .push_page(DescribeAffectedAccountsForOrganizationResponse {
+                affected_accounts: Some(vec!["222222222222".into()]),
+                ..Default::default()
+            });
+
+        tokio::time::pause();
+        let event_metrics = scraper(&["--organization", "--affected-accounts"], backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let account_ids: Vec<_> = series(&event_metrics.events)
+            .into_iter()
+            .map(|labels| labels["account_id"].clone())
+            .collect();
+        assert_eq!(account_ids, vec!["111111111111", "222222222222"]);
+        assert!(backend.events.requests().is_empty());
+        assert_eq!(
+            backend.affected_accounts_for_organization.requests()[0].event_arn,
+            "arn:aws:health:global::event/IAM/ISSUE/1"
+        );
+    }
+
+    #[tokio::test]
+    async fn endpoint_failures_fail_over() {
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_error(RusotoError::HttpDispatch(HttpDispatchError::new(
+                "connection refused".into(),
+            )));
+        let failover_backend = Arc::new(FakeBackend::default());
+        failover_backend
+            .events
+            .push_page(page(vec![event("FAILOVER", "open")], None));
+        let mut scraper = scraper(&[], backend);
+        scraper.failover = Some(Box::new(ScraperInner {
+            backend: failover_backend,
+            health_region: Region::UsEast2,
+            ..scraper.clone()
+        }));
+
+        let event_metrics = scraper.describe_events().await.unwrap();
+
+        assert_eq!(series(&event_metrics.events).len(), 1);
+        assert_eq!(
+            series(event_metrics.endpoint.as_ref().unwrap()),
+            vec![labels(&[("health_region", "us-east-2")])]
+        );
+    }
+
+    #[tokio::test]
+    async fn hanging_endpoints_fail_over() {
+        tokio::time::pause();
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_page(page(vec![event("FIRST_PAGE", "open")], Some("token")))
+            .push_hang();
+        let failover_backend = Arc::new(FakeBackend::default());
+        failover_backend
+            .events
+            .push_page(page(vec![event("FAILOVER", "open")], None));
+        let timeout = Duration::from_secs(30);
+        let mut scraper = scraper(&[], Arc::new(TimeoutBackend::new(backend, timeout)));
+        scraper.failover = Some(Box::new(ScraperInner {
+            backend: Arc::new(TimeoutBackend::new(failover_backend.clone(), timeout)),
+            health_region: Region::UsEast2,
+            ..scraper.clone()
+        }));
+
+        let event_metrics = scraper.describe_events().await.unwrap();
+
+        assert_eq!(failover_backend.events.requests().len(), 1);
+        assert_eq!(
+            series(&event_metrics.events)[0]["event_type_code"],
+            "FAILOVER"
+        );
+        assert_eq!(
+            series(event_metrics.endpoint.as_ref().unwrap()),
+            vec![labels(&[("health_region", "us-east-2")])]
+        );
+    }
+
+    /// Backend whose organizational view has the given status
+    fn organization_backend(status: &str) -> Arc<FakeBackend> {
+        let backend = Arc::new(FakeBackend::default());
+        backend.health_service_status_for_organization.push_page(
+            DescribeHealthServiceStatusForOrganizationResponse {
+                health_service_access_status_for_organization: Some(status.into()),
+            },
+        );
+        backend
+    }
+
+    #[tokio::test]
+    async fn disabled_organization_view_only_skips_its_account() {
+        let config = Config::parse(["aws_health_exporter", "--organization"]).unwrap();
+        let enabled = organization_backend(organization::ENABLED_STATUS);
+        let disabled = organization_backend("DISABLED");
+        let unchecked = Arc::new(FakeBackend::default());
+        unchecked
+            .health_service_status_for_organization
+            .push_error(RusotoError::Validation("access denied".into()));
+        let target = |account_id: &str, backend: &Arc<FakeBackend>| {
+            let account = AccountRole {
+                role: format!("arn:aws:iam::{}:role/aws-health-exporter", account_id),
+                account_id: account_id.to_string(),
+                name: None,
+            };
+            ScraperInner::with_backend(&config, Some(&account), backend.clone(), Region::UsEast1)
+        };
+        let scraper = Scraper {
+            targets: Arc::new(vec![
+                target("111111111111", &enabled),
+                target("222222222222", &disabled),
+                target("333333333333", &unchecked),
+            ]),
+            in_flight: SingleFlight::default(),
+        };
+
+        let all_event_metrics = scraper.describe_events().await.unwrap();
+
+        let account_success: Vec<_> = all_event_metrics
+            .iter()
+            .map(|event_metrics| event_metrics.account_success.as_ref().unwrap().get())
+            .collect();
+        assert_eq!(account_success, vec![1, 0, 1]);
+        assert_eq!(enabled.events_for_organization.requests().len(), 1);
+        assert!(disabled.events_for_organization.requests().is_empty());
+        assert_eq!(unchecked.events_for_organization.requests().len(), 1);
+        let organization_views: Vec<_> = scraper
+            .organization_view_metrics()
+            .unwrap()
+            .iter()
+            .map(|organization_view| {
+                let account_id = series(organization_view)[0]["account_id"].clone();
+                (account_id, organization_view.get())
+            })
+            .collect();
+        assert_eq!(
+            organization_views,
+            vec![
+                ("111111111111".to_string(), 1),
+                ("222222222222".to_string(), 0)
+            ]
+        );
+    }
+
+    fn fixtures(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[tokio::test]
+    async fn recorded_events_are_mapped_to_labels() {
+        let backend = Arc::new(ReplayBackend::new(fixtures("events")));
+
+        let event_metrics = scraper(&[], backend).describe_events().await.unwrap();
+
+        assert_eq!(
+            series(&event_metrics.events),
+            vec![
+                labels(&[
+                    ("availability_zone", ""),
+                    ("event_type_category", "issue"),
+                    ("event_type_code", "AWS_IAM_OPERATIONAL_ISSUE"),
+                    ("region", "global"),
+                    ("service", "IAM"),
+                    ("status", "open"),
+                ]),
+                labels(&[
+                    ("availability_zone", ""),
+                    ("event_type_category", "scheduledChange"),
+                    ("event_type_code", "AWS_RDS_MAINTENANCE_SCHEDULED"),
+                    ("region", "eu-west-1"),
+                    ("service", "RDS"),
+                    ("status", "upcoming"),
+                ]),
+                labels(&[
+                    ("availability_zone", "us-east-1a"),
+                    ("event_type_category", "issue"),
+                    ("event_type_code", "AWS_EC2_OPERATIONAL_ISSUE"),
+                    ("region", "us-east-1"),
+                    ("service", "EC2"),
+                    ("status", "closed"),
+                ]),
+            ]
+        );
+        // Only the closed event has an end time
+        assert_eq!(series(&event_metrics.end_times).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn recorded_organization_events_are_mapped_to_labels() {
+        let backend = Arc::new(ReplayBackend::new(fixtures("organization")));
+
+        let event_metrics = scraper(&["--organization"], backend)
+            .describe_events()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            series(&event_metrics.events),
+            vec![labels(&[
+                ("event_type_category", "scheduledChange"),
+                ("event_type_code", "AWS_EC2_INSTANCE_RETIREMENT_SCHEDULED"),
+                ("region", "us-east-1"),
+                ("service", "EC2"),
+                ("status", "upcoming"),
+            ])]
+        );
+    }
+
+    #[tokio::test]
+    async fn recorded_pages_are_replayed() {
+        let dir = std::env::temp_dir().join(format!(
+            "aws_health_exporter-recording-{}",
+            std::process::id()
+        ));
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_page(page(vec![event("FIRST", "open")], Some("token-1")))
+            .push_page(page(vec![event("SECOND", "open")], Some("token-2")))
+            .push_page(page(vec![event("THIRD", "open")], None))
+            // The next walk has fewer pages
+            .push_page(page(vec![event("ONLY", "closed")], None));
+        let recording = Arc::new(RecordingBackend::new(backend, dir.clone()));
+        let recording_scraper = scraper(&[], recording);
+
+        let recorded = recording_scraper.describe_events().await.unwrap();
+        let replayed = scraper(&[], Arc::new(ReplayBackend::new(dir.clone())))
+            .describe_events()
+            .await
+            .unwrap();
+        assert_eq!(series(&replayed.events), series(&recorded.events));
+        assert_eq!(series(&replayed.events).len(), 3);
+
+        let recorded = recording_scraper.describe_events().await.unwrap();
+        let replayed = scraper(&[], Arc::new(ReplayBackend::new(dir.clone())))
+            .describe_events()
+            .await
+            .unwrap();
+        assert_eq!(series(&replayed.events), series(&recorded.events));
+        assert!(!dir.join("DescribeEvents-0001.json").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn probes_are_replayed_from_a_directory_per_role() {
+        let dir =
+            std::env::temp_dir().join(format!("aws_health_exporter-probes-{}", std::process::id()));
+        let role = "arn:aws:iam::123456789012:role/path/aws-health-exporter";
+        let role_dir = dir.join("probe/arn_aws_iam__123456789012_role_path_aws-health-exporter");
+        std::fs::create_dir_all(&role_dir).unwrap();
+        for page in std::fs::read_dir(fixtures("events")).unwrap() {
+            let page = page.unwrap();
+            std::fs::copy(page.path(), role_dir.join(page.file_name())).unwrap();
+        }
+        let config = Config::parse(["aws_health_exporter", "--replay-dir", dir.to_str().unwrap()]);
+
+        let probed = Scraper::for_role(&config.unwrap(), role)
+            .unwrap()
+            .describe_events()
+            .await;
+
+        std::fs::remove_dir_all(dir).unwrap();
+        let replayed = scraper(&[], Arc::new(ReplayBackend::new(fixtures("events"))))
+            .describe_events()
+            .await
+            .unwrap();
+        assert_eq!(series(&probed.unwrap()[0].events), series(&replayed.events));
+    }
+
+    fn details(event: &Event, description: &str) -> EventDetails {
+        EventDetails {
+            event: Some(event.clone()),
+            event_description: Some(EventDescription {
+                latest_description: Some(description.into()),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn descriptions_of_open_events_are_exported() {
+        let open = event("OPEN", "open");
+        let failing = event("FAILING", "upcoming");
+        let backend = Arc::new(FakeBackend::default());
+        backend.events.push_page(page(
+            vec![open.clone(), failing.clone(), event("CLOSED", "closed")],
+            None,
+        ));
+        let long_description = format!("Increased error rates. {}", "x".repeat(300));
+        backend
+            .event_details
+            .push_throttling()
+            .push_page(DescribeEventDetailsResponse {
+                successful_set: Some(vec![details(&open, &long_description)]),
+                failed_set: Some(vec![EventDetailsErrorItem {
+                    event_arn: failing.arn.clone(),
+                    error_message: Some("Event not found".into()),
+                    ..Default::default()
+                }]),
+            });
+
+        tokio::time::pause();
+        let event_metrics = scraper(&["--event-details"], backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let requests = backend.event_details.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].event_arns,
+            vec![open.arn.clone().unwrap(), failing.arn.unwrap()]
+        );
+        let descriptions = series(event_metrics.event_descriptions.as_ref().unwrap());
+        assert_eq!(descriptions.len(), 1);
+        assert_eq!(descriptions[0]["event_arn"], open.arn.unwrap());
+        assert_eq!(descriptions[0]["event_type_code"], "OPEN");
+        assert_eq!(descriptions[0]["description"].chars().count(), 256);
+        assert!(long_description.starts_with(&descriptions[0]["description"]));
+        assert_eq!(descriptions[0]["description_hash"].len(), 16);
+        assert_eq!(
+            event_metrics.event_details_failures.as_ref().unwrap().get(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn organization_descriptions_are_queried_per_affected_account() {
+        let public = OrganizationEvent {
+            arn: Some("arn:aws:health:global::event/IAM/ISSUE/1".into()),
+            event_scope_code: Some("PUBLIC".into()),
+            event_type_code: Some("PUBLIC".into()),
+            status_code: Some("open".into()),
+            ..Default::default()
+        };
+        let account_specific = OrganizationEvent {
+            arn: Some("arn:aws:health:us-east-1::event/EC2/RETIREMENT/2".into()),
+            event_scope_code: Some("ACCOUNT_SPECIFIC".into()),
+            event_type_code: Some("ACCOUNT_SPECIFIC".into()),
+            status_code: Some("upcoming".into()),
+            ..Default::default()
+        };
+        let backend = organization_backend(organization::ENABLED_STATUS);
+        backend
+            .events_for_organization
+            .push_page(DescribeEventsForOrganizationResponse {
+                events: Some(vec![public, account_specific]),
+                next_token: None,
+            });
+        backend
+            .affected_accounts_for_organization
+            .push_page(DescribeAffectedAccountsForOrganizationResponse::default())
+            .push_page(DescribeAffectedAccountsForOrganizationResponse {
+                affected_accounts: Some(vec!["111111111111".into(), "222222222222".into()]),
+                ..Default::default()
+            });
+        let account_specific_event = Event {
+            arn: Some("arn:aws:health:us-east-1::event/EC2/RETIREMENT/2".into()),
+            ..Default::default()
+        };
+        backend.event_details_for_organization.push_page(
+            DescribeEventDetailsForOrganizationResponse {
+                successful_set: Some(vec![
+                    details(&account_specific_event, "Retirement of your instance"),
+                    details(&account_specific_event, "Retirement of your instance"),
+                ]),
+                failed_set: None,
+            },
+        );
+
+        let event_metrics = scraper(
+            &["--organization", "--affected-accounts", "--event-details"],
+            backend.clone(),
+        )
+        .describe_events()
+        .await
+        .unwrap();
+
+        let filters: Vec<_> = backend.event_details_for_organization.requests()[0]
+            .organization_event_detail_filters
+            .iter()
+            .map(|filter| (filter.event_arn.clone(), filter.aws_account_id.clone()))
+            .collect();
+        assert_eq!(
+            filters,
+            vec![
+                ("arn:aws:health:global::event/IAM/ISSUE/1".into(), None),
+                (
+                    "arn:aws:health:us-east-1::event/EC2/RETIREMENT/2".into(),
+                    Some("111111111111".into())
+                ),
+                (
+                    "arn:aws:health:us-east-1::event/EC2/RETIREMENT/2".into(),
+                    Some("222222222222".into())
+                ),
+            ]
+        );
+        let descriptions = series(event_metrics.event_descriptions.as_ref().unwrap());
+        assert_eq!(descriptions.len(), 1);
+        assert_eq!(
+            descriptions[0]["description"],
+            "Retirement of your instance"
+        );
+        assert_eq!(
+            event_metrics.event_details_failures.as_ref().unwrap().get(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn organization_detail_failures_are_counted_per_account() {
+        let event_arn = "arn:aws:health:us-east-1::event/EC2/RETIREMENT/2";
+        let backend = organization_backend(organization::ENABLED_STATUS);
+        backend
+            .events_for_organization
+            .push_page(DescribeEventsForOrganizationResponse {
+                events: Some(vec![OrganizationEvent {
+                    arn: Some(event_arn.into()),
+                    event_scope_code: Some("ACCOUNT_SPECIFIC".into()),
+                    event_type_code: Some("AWS_EC2_INSTANCE_RETIREMENT_SCHEDULED".into()),
+                    status_code: Some("upcoming".into()),
+                    ..Default::default()
+                }]),
+                next_token: None,
+            });
+        backend.affected_accounts_for_organization.push_page(
+            DescribeAffectedAccountsForOrganizationResponse {
+                affected_accounts: Some(vec![
+                    "111111111111".into(),
+                    "222222222222".into(),
+                    "333333333333".into(),
+                ]),
+                ..Default::default()
+            },
+        );
+        let failure = |account_id: &str| OrganizationEventDetailsErrorItem {
+            aws_account_id: Some(account_id.into()),
+            event_arn: Some(event_arn.into()),
+            error_message: Some("Account is no longer in the organization".into()),
+            ..Default::default()
+        };
+        let event = Event {
+            arn: Some(event_arn.into()),
+            ..Default::default()
+        };
+        backend.event_details_for_organization.push_page(
+            DescribeEventDetailsForOrganizationResponse {
+                successful_set: Some(vec![details(&event, "Retirement of your instance")]),
+                failed_set: Some(vec![failure("222222222222"), failure("333333333333")]),
+            },
+        );
+
+        let event_metrics = scraper(
+            &["--organization", "--affected-accounts", "--event-details"],
+            backend,
+        )
+        .describe_events()
+        .await
+        .unwrap();
+
+        let descriptions = series(event_metrics.event_descriptions.as_ref().unwrap());
+        assert_eq!(descriptions.len(), 1);
+        assert_eq!(
+            descriptions[0]["description"],
+            "Retirement of your instance"
+        );
+        assert_eq!(
+            event_metrics.event_details_failures.as_ref().unwrap().get(),
+            2
+        );
+    }
+
+    fn entity(event_arn: &str, value: &str, status: &str) -> AffectedEntity {
+        AffectedEntity {
+            entity_arn: Some(format!(
+                "arn:aws:health:us-east-1:111111111111:entity/{}",
+                value
+            )),
+            entity_value: Some(value.into()),
+            event_arn: Some(event_arn.into()),
+            status_code: Some(status.into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn entities_of_open_events_are_exported() {
+        let open = event("AWS_EC2_OPERATIONAL_ISSUE", "open");
+        let open_arn = open.arn.clone().unwrap();
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .events
+            .push_page(page(vec![open, event("CLOSED", "closed")], None));
+        backend
+            .affected_entities
+            .push_page(DescribeAffectedEntitiesResponse {
+                entities: Some(vec![entity(&open_arn, "i-1", "IMPAIRED")]),
+                next_token: Some("token-1".into()),
+            })
+            .push_page(DescribeAffectedEntitiesResponse {
+                entities: Some(vec![entity(&open_arn, "i-2", "UNIMPAIRED")]),
+                next_token: None,
+            });
+
+        let event_metrics = scraper(&["--affected-entities"], backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let entity_labels = |value: &str, status: &str| {
+            labels(&[
+                (
+                    "entity_arn",
+                    &format!("arn:aws:health:us-east-1:111111111111:entity/{}", value),
+                ),
+                ("entity_value", value),
+                ("event_type_code", "AWS_EC2_OPERATIONAL_ISSUE"),
+                ("status", status),
+            ])
+        };
+        assert_eq!(
+            series(event_metrics.affected_entities.as_ref().unwrap()),
+            vec![
+                entity_labels("i-1", "IMPAIRED"),
+                entity_labels("i-2", "UNIMPAIRED")
+            ]
+        );
+        let requests = backend.affected_entities.requests();
+        assert_eq!(requests[0].filter.event_arns, vec![open_arn]);
+        assert_eq!(requests[1].next_token, Some("token-1".into()));
+    }
+
+    #[tokio::test]
+    async fn organization_entities_are_queried_per_affected_account() {
+        let event_arn = "arn:aws:health:us-east-1::event/EC2/ISSUE/1";
+        let backend = organization_backend(organization::ENABLED_STATUS);
+        backend
+            .events_for_organization
+            .push_page(DescribeEventsForOrganizationResponse {
+                events: Some(vec![OrganizationEvent {
+                    arn: Some(event_arn.into()),
+                    event_type_code: Some("AWS_EC2_OPERATIONAL_ISSUE".into()),
+                    status_code: Some("open".into()),
+                    ..Default::default()
+                }]),
+                next_token: None,
+            });
+        backend.affected_accounts_for_organization.push_page(
+            DescribeAffectedAccountsForOrganizationResponse {
+                affected_accounts: Some(vec!["111111111111".into(), "222222222222".into()]),
+                ..Default::default()
+            },
+        );
+        backend
+            .affected_entities_for_organization
+            .push_page(DescribeAffectedEntitiesForOrganizationResponse {
+                entities: Some(vec![entity(event_arn, "i-1", "IMPAIRED")]),
+                next_token: Some("token-1".into()),
+                ..Default::default()
+            })
+            .push_page(DescribeAffectedEntitiesForOrganizationResponse {
+                entities: Some(vec![entity(event_arn, "i-2", "IMPAIRED")]),
+                ..Default::default()
+            });
+
+        let event_metrics = scraper(
+            &[
+                "--organization",
+                "--affected-accounts",
+                "--affected-entities",
+            ],
+            backend.clone(),
+        )
+        .describe_events()
+        .await
+        .unwrap();
+
+        let entities = series(event_metrics.affected_entities.as_ref().unwrap());
+        let values: Vec<_> = entities
+            .iter()
+            .map(|labels| labels["entity_value"].as_str())
+            .collect();
+        assert_eq!(values, vec!["i-1", "i-2"]);
+        assert!(entities
+            .iter()
+            .all(|labels| labels["event_type_code"] == "AWS_EC2_OPERATIONAL_ISSUE"));
+        let requests = backend.affected_entities_for_organization.requests();
+        let account_ids: Vec<_> = requests[0]
+            .organization_entity_filters
+            .iter()
+            .map(|filter| filter.aws_account_id.as_deref().unwrap())
+            .collect();
+        assert_eq!(account_ids, vec!["111111111111", "222222222222"]);
+        assert_eq!(requests[1].next_token, Some("token-1".into()));
+        assert!(backend.affected_entities.requests().is_empty());
+    }
+
+    fn aggregate(value: &str, count: i64) -> EventAggregate {
+        EventAggregate {
+            aggregate_value: Some(value.into()),
+            count: Some(count),
+        }
+    }
+
+    #[tokio::test]
+    async fn event_counts_are_exported_per_aggregate_value() {
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .event_aggregates
+            .push_page(DescribeEventAggregatesResponse {
+                event_aggregates: Some(vec![aggregate("issue", 3)]),
+                next_token: Some("token-1".into()),
+            })
+            .push_page(DescribeEventAggregatesResponse {
+                event_aggregates: Some(vec![aggregate("scheduledChange", 5)]),
+                next_token: None,
+            });
+
+        let args = ["--aggregate-by", "eventTypeCategory", "--no-events"];
+        let event_metrics = scraper(&args, backend.clone())
+            .describe_events()
+            .await
+            .unwrap();
+
+        let mut counts: Vec<(String, i64)> = event_metrics
+            .event_counts
+            .as_ref()
+            .unwrap()
+            .collect()
+            .iter()
+            .flat_map(|family| family.get_metric())
+            .map(|metric| {
+                let value = metric
+                    .get_label()
+                    .iter()
+                    .find(|label| label.get_name() == "aggregate_value")
+                    .unwrap()
+                    .get_value();
+                (value.to_string(), metric.get_gauge().get_value() as i64)
+            })
+            .collect();
+        counts.sort();
+        assert_eq!(
+            counts,
+            vec![("issue".into(), 3), ("scheduledChange".into(), 5)]
+        );
+        assert_eq!(
+            series(event_metrics.event_counts.as_ref().unwrap())[0]["aggregate_field"],
+            "eventTypeCategory"
+        );
+        let requests = backend.event_aggregates.requests();
+        assert_eq!(requests[0].aggregate_field, "eventTypeCategory");
+        assert_eq!(
+            requests[0].filter.as_ref().unwrap().event_type_categories,
+            Some(vec!["issue".to_string(), "scheduledChange".to_string()])
+        );
+        assert_eq!(requests[1].next_token, Some("token-1".into()));
+        assert!(backend.events.requests().is_empty());
+    }
+
+    fn event_types_page(services: &[&str]) -> event_types::DescribeEventTypesResponse {
+        let event_types = services
+            .iter()
+            .map(|service| event_types::EventType {
+                service: Some(service.to_string()),
+                code: Some(format!("AWS_{}_OPERATIONAL_ISSUE", service)),
+                category: Some("issue".into()),
+            })
+            .collect();
+        event_types::DescribeEventTypesResponse {
+            event_types: Some(event_types),
+            next_token: None,
+        }
+    }
+
+    fn service_scraper(args: &[&str], backend: Arc<FakeBackend>) -> Scraper {
+        let args = ["aws_health_exporter"].iter().chain(args);
+        Scraper::with_fake_backend(&Config::parse(args).unwrap(), backend)
+    }
+
+    #[tokio::test]
+    async fn known_services_are_valid() {
+        let backend = Arc::new(FakeBackend::default());
+        backend
+            .event_types
+            .push_page(event_types_page(&["EC2", "S3"]));
+        let args = ["--service", "EC2", "--service", "S3"];
+
+        service_scraper(&args, backend.clone())
+            .validate_services()
+            .await
+            .unwrap();
+
+        let filter = backend.event_types.requests()[0].filter.clone().unwrap();
+        assert_eq!(filter.services, Some(vec!["EC2".into(), "S3".into()]));
+    }
+
+    #[tokio::test]
+    async fn misspelled_services_are_rejected() {
+        let backend = Arc::new(FakeBackend::default());
+        backend.event_types.push_page(event_types_page(&["EC2"]));
+        let args = ["--service", "EC2", "--service", "EC3"];
+
+        let err = service_scraper(&args, backend)
+            .validate_services()
+            .await
+            .unwrap_err();
+
+        assert!(matches!(&err, Error::UnknownServices(services) if services == &["EC3"]));
+        assert_eq!(err.to_string(), "Unknown services: EC3");
+    }
+
+    #[tokio::test]
+    async fn services_are_not_validated_without_filter() {
+        let backend = Arc::new(FakeBackend::default());
+
+        service_scraper(&[], backend.clone())
+            .validate_services()
+            .await
+            .unwrap();
+
+        assert!(backend.event_types.requests().is_empty());
     }
 }
@@ -0,0 +1,65 @@
+use log::warn;
+use prometheus::{opts, IntGauge};
+
+use super::error::{Error, Result};
+use super::ScraperInner;
+
+// Status of the organizational view once enabled, others being DISABLED and PENDING
+pub(super) static ENABLED_STATUS: &str = "ENABLED";
+
+impl ScraperInner {
+    /// Check that the organizational view is enabled, without which organization events can't be queried.
+    pub(super) async fn check_organization_view(&self) -> Result<()> {
+        let response = self
+            .backend
+            .describe_health_service_status_for_organization()
+            .await?;
+        match response.health_service_access_status_for_organization {
+            Some(status) if status == ENABLED_STATUS => Ok(()),
+            status => Err(Error::OrganizationViewDisabled(status.unwrap_or_default())),
+        }
+    }
+
+    /// Check the organizational view before retrieving organization events, keeping its status for the metric.
+    ///
+    /// Only fails if the view is disabled, failing to check it doesn't prevent retrieving the events.
+    pub(super) async fn require_organization_view(&self) -> Result<()> {
+        let enabled = match self.check_organization_view().await {
+            Ok(()) => true,
+            Err(err @ Error::OrganizationViewDisabled(_)) => {
+                *self.organization_view_enabled.lock().unwrap() = Some(false);
+                return Err(err);
+            }
+            Err(err) if self.account.is_some() => {
+                warn!(
+                    "Failed to check the organizational view of account {}: {}",
+                    self.account_description(),
+                    err
+                );
+                return Ok(());
+            }
+            Err(err) => {
+                warn!("Failed to check the organizational view: {}", err);
+                return Ok(());
+            }
+        };
+        *self.organization_view_enabled.lock().unwrap() = Some(enabled);
+        Ok(())
+    }
+
+    /// Status of the organizational view as of the last successful check, if any
+    pub(super) fn organization_view_metric(&self) -> Result<Option<IntGauge>> {
+        let enabled = match *self.organization_view_enabled.lock().unwrap() {
+            Some(enabled) => enabled,
+            None => return Ok(None),
+        };
+        let opts = opts!(
+            "aws_health_organization_view_enabled",
+            "Whether the organizational view of AWS Health is enabled"
+        )
+        .const_labels(self.const_labels());
+        let organization_view = IntGauge::with_opts(opts)?;
+        organization_view.set(enabled as i64);
+        Ok(Some(organization_view))
+    }
+}
@@ -0,0 +1,80 @@
+use std::future::Future;
+use std::result::Result as StdResult;
+use std::time::Duration;
+
+use log::debug;
+use rusoto_core::request::BufferedHttpResponse;
+use rusoto_core::RusotoError;
+use tokio::time::sleep;
+use warp::http::StatusCode;
+
+use super::error::{Error, Result};
+
+pub(super) trait PaginatedRequest {
+    fn set_next_token(&mut self, next_token: Option<String>);
+}
+
+pub(super) trait PaginatedResponse {
+    fn get_next_token(&self) -> Option<String>;
+}
+
+/// Walk through all the pages of an API call, handing each one to `handle_page`.
+pub(super) async fn paginate<Req, Resp, E, F, Fut, H>(
+    mut request: Req,
+    call: F,
+    mut handle_page: H,
+) -> Result<()>
+where
+    Req: PaginatedRequest + Clone,
+    Resp: PaginatedResponse,
+    F: Fn(Req) -> Fut,
+    Fut: Future<Output = StdResult<Resp, RusotoError<E>>>,
+    Error: From<RusotoError<E>>,
+    H: FnMut(Resp) -> Result<()>,
+{
+    loop {
+        let response = retry_throttled(&request, &call).await?;
+        let next_token = response.get_next_token();
+        handle_page(response)?;
+        match next_token {
+            Some(token) => request.set_next_token(Some(token)),
+            None => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Make an API call, retrying it while it's throttled.
+///
+/// Throttled calls are retried with a poor man's exponential backoff,
+/// as documented on "Handling errors / Error retries and exponential backoff"
+/// https://docs.aws.amazon.com/elastictranscoder/latest/developerguide/error-handling.html#api-retries
+pub(super) async fn retry_throttled<Req, Resp, E, F, Fut>(request: &Req, call: &F) -> Result<Resp>
+where
+    Req: Clone,
+    F: Fn(Req) -> Fut,
+    Fut: Future<Output = StdResult<Resp, RusotoError<E>>>,
+    Error: From<RusotoError<E>>,
+{
+    let mut retry: u32 = 0;
+    let wait_base: u32 = 2;
+    loop {
+        if retry > 10 {
+            return Err(Error::TooManyRetries);
+        }
+        if retry > 0 {
+            let delay = Duration::from_millis(50) * wait_base.pow(retry);
+            debug!("Got TooManyRequests. Sleeping for {:#?}...", delay);
+            sleep(delay).await;
+        }
+        match call(request.clone()).await {
+            Ok(response) => return Ok(response),
+            Err(RusotoError::Unknown(BufferedHttpResponse {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                ..
+            })) => retry += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use log::{debug, warn};
+use rusoto_core::{RusotoError, RusotoResult};
+use rusoto_health::{
+    DescribeAffectedAccountsForOrganizationError, DescribeAffectedAccountsForOrganizationRequest,
+    DescribeAffectedAccountsForOrganizationResponse, DescribeAffectedEntitiesError,
+    DescribeAffectedEntitiesForOrganizationError, DescribeAffectedEntitiesForOrganizationRequest,
+    DescribeAffectedEntitiesForOrganizationResponse, DescribeAffectedEntitiesRequest,
+    DescribeAffectedEntitiesResponse, DescribeEventAggregatesError, DescribeEventAggregatesRequest,
+    DescribeEventAggregatesResponse, DescribeEventDetailsError,
+    DescribeEventDetailsForOrganizationError, DescribeEventDetailsForOrganizationRequest,
+    DescribeEventDetailsRequest, DescribeEventTypesError, DescribeEventTypesRequest,
+    DescribeEventsError, DescribeEventsForOrganizationError, DescribeEventsForOrganizationRequest,
+    DescribeEventsForOrganizationResponse, DescribeEventsRequest, DescribeEventsResponse,
+    DescribeHealthServiceStatusForOrganizationError,
+    DescribeHealthServiceStatusForOrganizationResponse,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::backend::HealthBackend;
+use super::details::{DescribeEventDetailsForOrganizationResponse, DescribeEventDetailsResponse};
+use super::event_types::DescribeEventTypesResponse;
+use super::organization::ENABLED_STATUS;
+use super::pagination::PaginatedResponse;
+
+static DESCRIBE_EVENTS: &str = "DescribeEvents";
+static DESCRIBE_EVENTS_FOR_ORGANIZATION: &str = "DescribeEventsForOrganization";
+
+/// Path of a page of an operation, numbered from 0 in the order of the walk
+fn page_path(dir: &Path, operation: &str, index: usize) -> PathBuf {
+    dir.join(format!("{}-{:04}.json", operation, index))
+}
+
+/// Writes the pages of events to a directory, while passing all the calls to another backend.
+///
+/// Each walk through the pages replaces the previous recording of the operation.
+pub(super) struct RecordingBackend {
+    inner: Arc<dyn HealthBackend>,
+    dir: PathBuf,
+    /// Index of the last page recorded, by operation
+    last_pages: Mutex<HashMap<&'static str, usize>>,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: Arc<dyn HealthBackend>, dir: PathBuf) -> Self {
+        Self {
+            inner,
+            dir,
+            last_pages: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a page, only logging failures so that they don't prevent the refresh.
+    fn record<T: Serialize>(&self, operation: &'static str, first_page: bool, response: &T) {
+        let index = {
+            let mut last_pages = self.last_pages.lock().unwrap();
+            let index = match last_pages.get(operation) {
+                Some(last_page) if !first_page => last_page + 1,
+                _ => 0,
+            };
+            last_pages.insert(operation, index);
+            index
+        };
+        if let Err(err) = self.write_page(operation, index, response) {
+            warn!(
+                "Failed to record {} page in {}: {}",
+                operation,
+                self.dir.display(),
+                err
+            );
+        }
+    }
+
+    fn write_page<T: Serialize>(
+        &self,
+        operation: &str,
+        index: usize,
+        response: &T,
+    ) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|err| err.to_string())?;
+        if index == 0 {
+            self.remove_pages(operation)?;
+        }
+        let path = page_path(&self.dir, operation, index);
+        let content = serde_json::to_string_pretty(response).map_err(|err| err.to_string())?;
+        fs::write(&path, content).map_err(|err| format!("{}: {}", path.display(), err))?;
+        debug!("Recorded {}", path.display());
+        Ok(())
+    }
+
+    /// Remove the pages of the previous walk, which may have had more of them
+    fn remove_pages(&self, operation: &str) -> Result<(), String> {
+        let prefix = format!("{}-", operation);
+        for entry in fs::read_dir(&self.dir).map_err(|err| err.to_string())? {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            let is_page = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".json"));
+            if is_page {
+                fs::remove_file(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HealthBackend for RecordingBackend {
+    async fn describe_events(
+        &self,
+        input: DescribeEventsRequest,
+    ) -> RusotoResult<DescribeEventsResponse, DescribeEventsError> {
+        let first_page = input.next_token.is_none();
+        let response = self.inner.describe_events(input).await?;
+        self.record(DESCRIBE_EVENTS, first_page, &response);
+        Ok(response)
+    }
+
+    async fn describe_events_for_organization(
+        &self,
+        input: DescribeEventsForOrganizationRequest,
+    ) -> RusotoResult<DescribeEventsForOrganizationResponse, DescribeEventsForOrganizationError>
+    {
+        let first_page = input.next_token.is_none();
+        let response = self.inner.describe_events_for_organization(input).await?;
+        self.record(DESCRIBE_EVENTS_FOR_ORGANIZATION, first_page, &response);
+        Ok(response)
+    }
+
+    async fn describe_affected_accounts_for_organization(
+        &self,
+        input: DescribeAffectedAccountsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedAccountsForOrganizationError,
+    > {
+        self.inner
+            .describe_affected_accounts_for_organization(input)
+            .await
+    }
+
+    async fn describe_affected_entities(
+        &self,
+        input: DescribeAffectedEntitiesRequest,
+    ) -> RusotoResult<DescribeAffectedEntitiesResponse, DescribeAffectedEntitiesError> {
+        self.inner.describe_affected_entities(input).await
+    }
+
+    async fn describe_affected_entities_for_organization(
+        &self,
+        input: DescribeAffectedEntitiesForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedEntitiesForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationError,
+    > {
+        self.inner
+            .describe_affected_entities_for_organization(input)
+            .await
+    }
+
+    async fn describe_event_aggregates(
+        &self,
+        input: DescribeEventAggregatesRequest,
+    ) -> RusotoResult<DescribeEventAggregatesResponse, DescribeEventAggregatesError> {
+        self.inner.describe_event_aggregates(input).await
+    }
+
+    async fn describe_event_details(
+        &self,
+        input: DescribeEventDetailsRequest,
+    ) -> RusotoResult<DescribeEventDetailsResponse, DescribeEventDetailsError> {
+        self.inner.describe_event_details(input).await
+    }
+
+    async fn describe_event_details_for_organization(
+        &self,
+        input: DescribeEventDetailsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeEventDetailsForOrganizationResponse,
+        DescribeEventDetailsForOrganizationError,
+    > {
+        self.inner
+            .describe_event_details_for_organization(input)
+            .await
+    }
+
+    async fn describe_event_types(
+        &self,
+        input: DescribeEventTypesRequest,
+    ) -> RusotoResult<DescribeEventTypesResponse, DescribeEventTypesError> {
+        self.inner.describe_event_types(input).await
+    }
+
+    async fn describe_health_service_status_for_organization(
+        &self,
+    ) -> RusotoResult<
+        DescribeHealthServiceStatusForOrganizationResponse,
+        DescribeHealthServiceStatusForOrganizationError,
+    > {
+        self.inner
+            .describe_health_service_status_for_organization()
+            .await
+    }
+}
+
+/// Serves the pages of events recorded in a directory, without calling AWS.
+///
+/// Pages are read on each call, so a new recording is picked up at the next refresh.
+pub(super) struct ReplayBackend {
+    dir: PathBuf,
+}
+
+impl ReplayBackend {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn read_page<T: DeserializeOwned>(&self, operation: &str, index: usize) -> Result<T, String> {
+        let path = page_path(&self.dir, operation, index);
+        let content = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to replay {}: {}", path.display(), err))?;
+        serde_json::from_str(&content)
+            .map_err(|err| format!("Failed to replay {}: {}", path.display(), err))
+    }
+
+    /// Find the page following the one which returned `next_token`, or the first one.
+    fn replay<T>(&self, operation: &str, next_token: Option<&str>) -> Result<T, String>
+    where
+        T: DeserializeOwned + PaginatedResponse,
+    {
+        let next_token = match next_token {
+            Some(next_token) => next_token,
+            None => return self.read_page(operation, 0),
+        };
+        let mut index = 0;
+        loop {
+            let page: T = self.read_page(operation, index)?;
+            index += 1;
+            if page.get_next_token().as_deref() == Some(next_token) {
+                return self.read_page(operation, index);
+            }
+        }
+    }
+}
+
+fn not_recorded(operation: &str) -> String {
+    format!("{} isn't recorded, so it can't be replayed", operation)
+}
+
+#[async_trait]
+impl HealthBackend for ReplayBackend {
+    async fn describe_events(
+        &self,
+        input: DescribeEventsRequest,
+    ) -> RusotoResult<DescribeEventsResponse, DescribeEventsError> {
+        self.replay(DESCRIBE_EVENTS, input.next_token.as_deref())
+            .map_err(RusotoError::Validation)
+    }
+
+    async fn describe_events_for_organization(
+        &self,
+        input: DescribeEventsForOrganizationRequest,
+    ) -> RusotoResult<DescribeEventsForOrganizationResponse, DescribeEventsForOrganizationError>
+    {
+        self.replay(
+            DESCRIBE_EVENTS_FOR_ORGANIZATION,
+            input.next_token.as_deref(),
+        )
+        .map_err(RusotoError::Validation)
+    }
+
+    async fn describe_affected_accounts_for_organization(
+        &self,
+        _input: DescribeAffectedAccountsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedAccountsForOrganizationResponse,
+        DescribeAffectedAccountsForOrganizationError,
+    > {
+        Err(RusotoError::Validation(not_recorded(
+            "DescribeAffectedAccountsForOrganization",
+        )))
+    }
+
+    async fn describe_affected_entities(
+        &self,
+        _input: DescribeAffectedEntitiesRequest,
+    ) -> RusotoResult<DescribeAffectedEntitiesResponse, DescribeAffectedEntitiesError> {
+        Err(RusotoError::Validation(not_recorded(
+            "DescribeAffectedEntities",
+        )))
+    }
+
+    async fn describe_affected_entities_for_organization(
+        &self,
+        _input: DescribeAffectedEntitiesForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeAffectedEntitiesForOrganizationResponse,
+        DescribeAffectedEntitiesForOrganizationError,
+    > {
+        Err(RusotoError::Validation(not_recorded(
+            "DescribeAffectedEntitiesForOrganization",
+        )))
+    }
+
+    async fn describe_event_aggregates(
+        &self,
+        _input: DescribeEventAggregatesRequest,
+    ) -> RusotoResult<DescribeEventAggregatesResponse, DescribeEventAggregatesError> {
+        Err(RusotoError::Validation(not_recorded(
+            "DescribeEventAggregates",
+        )))
+    }
+
+    async fn describe_event_details(
+        &self,
+        _input: DescribeEventDetailsRequest,
+    ) -> RusotoResult<DescribeEventDetailsResponse, DescribeEventDetailsError> {
+        Err(RusotoError::Validation(not_recorded(
+            "DescribeEventDetails",
+        )))
+    }
+
+    async fn describe_event_details_for_organization(
+        &self,
+        _input: DescribeEventDetailsForOrganizationRequest,
+    ) -> RusotoResult<
+        DescribeEventDetailsForOrganizationResponse,
+        DescribeEventDetailsForOrganizationError,
+    > {
+        Err(RusotoError::Validation(not_recorded(
+            "DescribeEventDetailsForOrganization",
+        )))
+    }
+
+    async fn describe_event_types(
+        &self,
+        _input: DescribeEventTypesRequest,
+    ) -> RusotoResult<DescribeEventTypesResponse, DescribeEventTypesError> {
+        Err(RusotoError::Validation(not_recorded("DescribeEventTypes")))
+    }
+
+    /// Organization events can only have been recorded with the organizational view enabled
+    async fn describe_health_service_status_for_organization(
+        &self,
+    ) -> RusotoResult<
+        DescribeHealthServiceStatusForOrganizationResponse,
+        DescribeHealthServiceStatusForOrganizationError,
+    > {
+        Ok(DescribeHealthServiceStatusForOrganizationResponse {
+            health_service_access_status_for_organization: Some(ENABLED_STATUS.to_string()),
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use futures::future::{BoxFuture, FutureExt, WeakShared};
+use std::future::Future;
+use std::sync::Mutex;
+
+/// Makes concurrent callers share a single execution of a future.
+///
+/// A caller arriving while a future is running joins it and gets a clone of its output instead of
+/// starting a new one. Once the future has completed, or all its callers gave up on it, the next
+/// caller starts a new one.
+pub(crate) struct SingleFlight<T> {
+    /// Only the callers keep the future alive, so that it's dropped when they all time out
+    in_flight: Mutex<Option<WeakShared<BoxFuture<'static, T>>>>,
+}
+
+impl<T> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self {
+            in_flight: Mutex::new(None),
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> SingleFlight<T> {
+    /// Waits for the running future, or starts a new one built by `f` if there is none.
+    pub async fn run<F, Fut>(&self, f: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T> + Send + 'static,
+    {
+        let future = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            let running = in_flight
+                .as_ref()
+                .and_then(WeakShared::upgrade)
+                .filter(|future| future.peek().is_none());
+            match running {
+                Some(future) => future,
+                None => {
+                    let future = f().boxed().shared();
+                    *in_flight = future.downgrade();
+                    future
+                }
+            }
+        };
+        future.await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::{sleep, timeout};
+
+    /// Stands in for a paginated walk of the API, counting how many times it was started.
+    async fn fake_walk(
+        walks: Arc<AtomicUsize>,
+        result: Result<u32, String>,
+    ) -> Result<u32, String> {
+        walks.fetch_add(1, Ordering::SeqCst);
+        sleep(Duration::from_millis(50)).await;
+        result
+    }
+
+    async fn run_concurrently(
+        single_flight: Arc<SingleFlight<Result<u32, String>>>,
+        walks: Arc<AtomicUsize>,
+        result: Result<u32, String>,
+        callers: usize,
+    ) -> Vec<Result<u32, String>> {
+        let handles: Vec<_> = (0..callers)
+            .map(|_| {
+                let single_flight = single_flight.clone();
+                let walks = walks.clone();
+                let result = result.clone();
+                tokio::spawn(
+                    async move { single_flight.run(move || fake_walk(walks, result)).await },
+                )
+            })
+            .collect();
+
+        let mut results = vec![];
+        for handle in handles {
+            results.push(handle.await.unwrap());
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_walk() {
+        let single_flight = Arc::new(SingleFlight::default());
+        let walks = Arc::new(AtomicUsize::new(0));
+
+        let results = run_concurrently(single_flight, walks.clone(), Ok(42), 50).await;
+
+        assert_eq!(walks.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| result == &Ok(42)));
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_the_error() {
+        let single_flight = Arc::new(SingleFlight::default());
+        let walks = Arc::new(AtomicUsize::new(0));
+
+        let error = Err("throttled".to_string());
+        let results = run_concurrently(single_flight, walks.clone(), error.clone(), 50).await;
+
+        assert_eq!(walks.load(Ordering::SeqCst), 1);
+        assert!(results.iter().all(|result| result == &error));
+    }
+
+    #[tokio::test]
+    async fn completed_walk_is_not_reused() {
+        let single_flight = Arc::new(SingleFlight::default());
+        let walks = Arc::new(AtomicUsize::new(0));
+
+        run_concurrently(single_flight.clone(), walks.clone(), Ok(1), 10).await;
+        let results = run_concurrently(single_flight, walks.clone(), Ok(2), 10).await;
+
+        assert_eq!(walks.load(Ordering::SeqCst), 2);
+        assert!(results.iter().all(|result| result == &Ok(2)));
+    }
+
+    #[tokio::test]
+    async fn abandoned_walk_is_not_joined() {
+        tokio::time::pause();
+        let single_flight = SingleFlight::default();
+
+        let hanging = single_flight.run(futures::future::pending::<Result<u32, String>>);
+        assert!(timeout(Duration::from_secs(300), hanging).await.is_err());
+        let result = single_flight.run(|| async { Ok(3) }).await;
+
+        assert_eq!(result, Ok(3));
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+
+use super::HealthEvent;
+use crate::config::AccountRole;
+
+/// Event as served by the JSON API, with the fields which don't fit in metric labels
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct EventSummary {
+    pub arn: Option<String>,
+    /// Account the event was retrieved from, when querying several accounts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account_name: Option<String>,
+    pub region: String,
+    pub availability_zone: Option<String>,
+    pub service: String,
+    pub event_type_category: String,
+    pub event_type_code: String,
+    pub status: String,
+    pub scope: Option<String>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub last_updated_time: Option<DateTime<Utc>>,
+    /// Accounts affected by an organization event, when retrieved
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub affected_accounts: Vec<String>,
+    /// Latest description, when retrieved
+    pub description: Option<String>,
+}
+
+impl EventSummary {
+    pub(super) fn new(event: &HealthEvent, account: Option<&AccountRole>) -> Self {
+        let field = |name: &str| event.fields.get(name).cloned().unwrap_or_default();
+        Self {
+            arn: event.arn.to_owned(),
+            account_id: account.map(|account| account.account_id.to_owned()),
+            account_name: account.and_then(|account| account.name.to_owned()),
+            region: field("region"),
+            availability_zone: event
+                .fields
+                .get("availability_zone")
+                .filter(|availability_zone| !availability_zone.is_empty())
+                .cloned(),
+            service: field("service"),
+            event_type_category: field("event_type_category"),
+            event_type_code: field("event_type_code"),
+            status: field("status"),
+            scope: event.scope.to_owned(),
+            start_time: date_time(event.start_time),
+            end_time: date_time(event.end_time),
+            last_updated_time: date_time(event.last_updated_time),
+            affected_accounts: event.account_ids.to_owned(),
+            description: event.description.to_owned(),
+        }
+    }
+}
+
+/// Convert a timestamp from the AWS API, in seconds
+fn date_time(seconds: Option<f64>) -> Option<DateTime<Utc>> {
+    seconds.and_then(|seconds| {
+        Utc.timestamp_millis_opt((seconds * 1000.0).round() as i64)
+            .single()
+    })
+}